@@ -0,0 +1,45 @@
+#!/usr/bin/env bash
+# Renders the built-in random spheres scene and prints the samples traced per second.
+#
+#   scripts/bench.sh                  benchmark the working tree
+#   scripts/bench.sh REV [REV...]     benchmark each git revision, e.g. before and after a change
+#
+# Each revision is built in release mode in a temporary worktree. The image size, sample count
+# and thread count can be overridden with WIDTH, SPP and THREADS; the defaults render
+# 300x168 pixels at 16 samples on one thread. The time covers the whole run, including building
+# the scene, which is negligible next to the render.
+set -euo pipefail
+
+WIDTH=${WIDTH:-300}
+SPP=${SPP:-16}
+THREADS=${THREADS:-1}
+HEIGHT=$((WIDTH * 9 / 16))
+
+root=$(git -C "$(dirname "$0")" rev-parse --show-toplevel)
+
+bench() {
+    local label=$1 binary=$2
+    local start end
+    start=$(date +%s.%N)
+    "$binary" -w "$WIDTH" -a 16:9 -s "$SPP" -t "$THREADS" --seed 0 >/dev/null 2>&1
+    end=$(date +%s.%N)
+    awk -v label="$label" -v start="$start" -v end="$end" -v samples=$((WIDTH * HEIGHT * SPP)) \
+        'BEGIN { t = end - start; printf "%-12s %8.2f s %12.0f samples/s\n", label, t, samples / t }'
+}
+
+echo "Random spheres scene, ${WIDTH}x${HEIGHT} pixels, $SPP samples per pixel, $THREADS thread(s)"
+
+if [ $# -eq 0 ]; then
+    cargo build --release --quiet --manifest-path "$root/Cargo.toml"
+    bench "working tree" "$root/target/release/raytracing"
+    exit 0
+fi
+
+work=$(mktemp -d)
+trap 'rm -rf "$work"; git -C "$root" worktree prune' EXIT
+for rev in "$@"; do
+    dir="$work/$rev"
+    git -C "$root" worktree add --quiet --detach "$dir" "$rev"
+    cargo build --release --quiet --manifest-path "$dir/Cargo.toml"
+    bench "$(git -C "$root" rev-parse --short "$rev")" "$dir/target/release/raytracing"
+done
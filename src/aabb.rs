@@ -0,0 +1,170 @@
+#![allow(dead_code)]
+use crate::interval::{self, Interval};
+use crate::ray::*;
+
+pub const EMPTY: Aabb = Aabb {
+    x: interval::EMPTY,
+    y: interval::EMPTY,
+    z: interval::EMPTY,
+};
+
+pub const UNIVERSE: Aabb = Aabb {
+    x: interval::UNIVERSAL,
+    y: interval::UNIVERSAL,
+    z: interval::UNIVERSAL,
+};
+
+// Axis-aligned bounding box
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub fn new() -> Self {
+        // The default AABB is empty, since intervals are empty by default.
+        EMPTY
+    }
+
+    pub fn new_use(x: Interval, y: Interval, z: Interval) -> Self {
+        Aabb { x, y, z }
+    }
+
+    pub fn new_from_points(a: Point3, b: Point3) -> Self {
+        // Treat the two points a and b as extrema for the bounding box, so we don't require a
+        // particular minimum/maximum coordinate order.
+        let x = if a[0] <= b[0] {
+            Interval::new_use(a[0], b[0])
+        } else {
+            Interval::new_use(b[0], a[0])
+        };
+        let y = if a[1] <= b[1] {
+            Interval::new_use(a[1], b[1])
+        } else {
+            Interval::new_use(b[1], a[1])
+        };
+        let z = if a[2] <= b[2] {
+            Interval::new_use(a[2], b[2])
+        } else {
+            Interval::new_use(b[2], a[2])
+        };
+
+        Aabb { x, y, z }
+    }
+
+    pub fn new_enclosing(box0: &Aabb, box1: &Aabb) -> Self {
+        Aabb {
+            x: Interval::new_enclosing(&box0.x, &box1.x),
+            y: Interval::new_enclosing(&box0.y, &box1.y),
+            z: Interval::new_enclosing(&box0.z, &box1.z),
+        }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, mut ray_t: Interval) -> bool {
+        let ray_orig = r.origin();
+        let ray_dir = r.direction();
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / ray_dir[axis];
+
+            let t0 = (ax.min - ray_orig[axis]) * adinv;
+            let t1 = (ax.max - ray_orig[axis]) * adinv;
+
+            if t0 < t1 {
+                if t0 > ray_t.min {
+                    ray_t.min = t0;
+                }
+                if t1 < ray_t.max {
+                    ray_t.max = t1;
+                }
+            } else {
+                if t1 > ray_t.min {
+                    ray_t.min = t1;
+                }
+                if t0 < ray_t.max {
+                    ray_t.max = t0;
+                }
+            }
+
+            if ray_t.max <= ray_t.min {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn padded(&self) -> Aabb {
+        // Adjust the AABB so that no side is narrower than some delta, padding if necessary.
+        // Flat primitives would otherwise produce boxes that rays can never hit.
+        let delta = 0.0001;
+        let pad = |i: &Interval| {
+            if i.size() < delta {
+                i.expand(delta)
+            } else {
+                *i
+            }
+        };
+        Aabb {
+            x: pad(&self.x),
+            y: pad(&self.y),
+            z: pad(&self.z),
+        }
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        // False for boxes of unbounded primitives such as infinite planes.
+        [self.x, self.y, self.z]
+            .iter()
+            .all(|i| i.min.is_finite() && i.max.is_finite())
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let dx = self.x.size();
+        let dy = self.y.size();
+        let dz = self.z.size();
+        if dx < 0.0 || dy < 0.0 || dz < 0.0 {
+            return 0.0;
+        }
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        Point3::new_use(
+            0.5 * (self.x.min + self.x.max),
+            0.5 * (self.y.min + self.y.max),
+            0.5 * (self.z.min + self.z.max),
+        )
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        // Returns the index of the longest axis of the bounding box.
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() {
+                0
+            } else {
+                2
+            }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
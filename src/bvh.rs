@@ -0,0 +1,598 @@
+#![allow(dead_code)]
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::aabb::Aabb;
+use crate::hittable::*;
+use crate::interval::*;
+use crate::ray::*;
+
+// Bounding volume hierarchy node. Leaves hold the primitives directly as children.
+#[derive(Clone)]
+pub struct BvhNode {
+    left: Hittable,
+    right: Hittable,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    pub fn new(list: HittableList) -> Self {
+        Self::new_use(list.into_objects())
+    }
+
+    pub fn new_use(mut objects: Vec<Hittable>) -> Self {
+        // Build the bounding box of the span of source objects.
+        let mut bbox = Aabb::new();
+        for object in &objects {
+            bbox = Aabb::new_enclosing(&bbox, &object.bounding_box());
+        }
+
+        let axis = bbox.longest_axis();
+
+        let (left, right) = match objects.len() {
+            0 => (
+                Hittable::HittableList(Box::default()),
+                Hittable::HittableList(Box::default()),
+            ),
+            1 => (
+                objects.pop().unwrap(),
+                Hittable::HittableList(Box::default()),
+            ),
+            2 => {
+                let b = objects.pop().unwrap();
+                let a = objects.pop().unwrap();
+                (a, b)
+            }
+            _ => {
+                objects.sort_by(|a, b| Self::box_compare(a, b, axis));
+
+                let right_objects = objects.split_off(objects.len() / 2);
+                (
+                    Hittable::Bvh(Box::new(BvhNode::new_use(objects))),
+                    Hittable::Bvh(Box::new(BvhNode::new_use(right_objects))),
+                )
+            }
+        };
+
+        BvhNode { left, right, bbox }
+    }
+
+    pub fn new_children(left: Hittable, right: Hittable) -> Self {
+        let bbox = Aabb::new_enclosing(&left.bounding_box(), &right.bounding_box());
+        BvhNode { left, right, bbox }
+    }
+
+    pub fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        if !self.bbox.hit(&r, ray_t) {
+            return false;
+        }
+
+        let hit_left = self.left.hit(r, ray_t, rec);
+        let hit_right = self.right.hit(
+            r,
+            Interval::new_use(ray_t.min, if hit_left { rec.t } else { ray_t.max }),
+            rec,
+        );
+
+        hit_left || hit_right
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    pub fn children(&self) -> [&Hittable; 2] {
+        [&self.left, &self.right]
+    }
+
+    fn box_compare(a: &Hittable, b: &Hittable, axis_index: usize) -> Ordering {
+        let a_axis_interval = *a.bounding_box().axis_interval(axis_index);
+        let b_axis_interval = *b.bounding_box().axis_interval(axis_index);
+        a_axis_interval
+            .min
+            .partial_cmp(&b_axis_interval.min)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SplitMethod {
+    // Sort along the longest axis and split the object list in half.
+    Median,
+    // Split at the middle of the centroid bounds along the longest axis.
+    Midpoint,
+    // Binned surface area heuristic.
+    Sah,
+}
+
+impl SplitMethod {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "median" => Some(SplitMethod::Median),
+            "midpoint" => Some(SplitMethod::Midpoint),
+            "sah" => Some(SplitMethod::Sah),
+            _ => None,
+        }
+    }
+}
+
+// Configurable BVH construction. The defaults build a binned SAH tree.
+#[derive(Clone)]
+pub struct BvhBuilder {
+    pub split_method: SplitMethod,
+    pub bin_count: usize,
+    pub max_leaf_size: usize,
+    pub traversal_cost: f64,
+    pub intersection_cost: f64,
+}
+
+impl BvhBuilder {
+    pub fn new() -> Self {
+        BvhBuilder {
+            split_method: SplitMethod::Sah,
+            bin_count: 16,
+            max_leaf_size: 4,
+            traversal_cost: 1.0,
+            intersection_cost: 1.0,
+        }
+    }
+
+    pub fn build(&self, list: HittableList) -> Hittable {
+        // Unbounded objects (infinite planes) can't be binned; they sit in a list next to the tree
+        // and are tested by every ray.
+        let (bounded, unbounded): (Vec<Hittable>, Vec<Hittable>) = list
+            .into_objects()
+            .into_iter()
+            .partition(|o| o.bounding_box().is_bounded());
+
+        let root = self.build_node(bounded);
+        if !unbounded.is_empty() {
+            return Hittable::Bvh(Box::new(BvhNode::new_children(
+                root,
+                Self::make_leaf(unbounded),
+            )));
+        }
+
+        // Always hand back a BVH root, even when everything fit into a single leaf.
+        match root {
+            Hittable::Bvh(_) => root,
+            leaf => Hittable::Bvh(Box::new(BvhNode::new_children(
+                leaf,
+                Hittable::HittableList(Box::default()),
+            ))),
+        }
+    }
+
+    pub fn stats(&self, root: &Hittable) -> BvhStats {
+        let mut stats = BvhStats::new();
+        let root_area = Self::bounded_area(root);
+        self.collect_stats(root, 1, root_area, &mut stats);
+        stats
+    }
+
+    fn build_node(&self, mut objects: Vec<Hittable>) -> Hittable {
+        let n = objects.len();
+        if n == 0 {
+            return Hittable::HittableList(Box::default());
+        }
+        if n == 1 {
+            return objects.pop().unwrap();
+        }
+
+        let mut bbox = Aabb::new();
+        let mut centroid_bounds = Aabb::new();
+        for object in &objects {
+            let object_box = object.bounding_box();
+            let c = object_box.centroid();
+            bbox = Aabb::new_enclosing(&bbox, &object_box);
+            centroid_bounds = Aabb::new_enclosing(&centroid_bounds, &Aabb::new_from_points(c, c));
+        }
+
+        let right_objects = match self.split_method {
+            SplitMethod::Median => {
+                if n <= self.max_leaf_size {
+                    return Self::make_leaf(objects);
+                }
+                Self::split_median(&mut objects, centroid_bounds.longest_axis())
+            }
+            SplitMethod::Midpoint => {
+                if n <= self.max_leaf_size {
+                    return Self::make_leaf(objects);
+                }
+                let axis = centroid_bounds.longest_axis();
+                let extent = centroid_bounds.axis_interval(axis);
+                let mid = 0.5 * (extent.min + extent.max);
+                let (left, right): (Vec<Hittable>, Vec<Hittable>) = objects
+                    .drain(..)
+                    .partition(|o| o.bounding_box().centroid()[axis] < mid);
+                objects = left;
+                if objects.is_empty() || right.is_empty() {
+                    objects.extend(right);
+                    Self::split_median(&mut objects, axis)
+                } else {
+                    right
+                }
+            }
+            SplitMethod::Sah => {
+                let leaf_cost = n as f64 * self.intersection_cost;
+                match self.find_sah_split(&objects, &bbox, &centroid_bounds) {
+                    Some((cost, axis, split)) if n > self.max_leaf_size || cost < leaf_cost => {
+                        let extent = *centroid_bounds.axis_interval(axis);
+                        let (left, right): (Vec<Hittable>, Vec<Hittable>) =
+                            objects.drain(..).partition(|o| {
+                                self.bin_index(o.bounding_box().centroid()[axis], &extent) <= split
+                            });
+                        objects = left;
+                        if objects.is_empty() || right.is_empty() {
+                            objects.extend(right);
+                            Self::split_median(&mut objects, axis)
+                        } else {
+                            right
+                        }
+                    }
+                    _ if n <= self.max_leaf_size => return Self::make_leaf(objects),
+                    // Every centroid coincides, so no binned split exists.
+                    _ => Self::split_median(&mut objects, bbox.longest_axis()),
+                }
+            }
+        };
+
+        let left = self.build_node(objects);
+        let right = self.build_node(right_objects);
+        Hittable::Bvh(Box::new(BvhNode::new_children(left, right)))
+    }
+
+    fn find_sah_split(
+        &self,
+        objects: &[Hittable],
+        bbox: &Aabb,
+        centroid_bounds: &Aabb,
+    ) -> Option<(f64, usize, usize)> {
+        // Returns the cheapest (cost, axis, last bin on the left side) over all three axes.
+        let bin_count = self.bin_count.max(2);
+        let parent_area = bbox.surface_area();
+        let mut best: Option<(f64, usize, usize)> = None;
+
+        for axis in 0..3 {
+            let extent = *centroid_bounds.axis_interval(axis);
+            if extent.size() <= 0.0 {
+                continue;
+            }
+
+            let mut bin_boxes = vec![Aabb::new(); bin_count];
+            let mut bin_counts = vec![0usize; bin_count];
+            for object in objects {
+                let object_box = object.bounding_box();
+                let b = self.bin_index(object_box.centroid()[axis], &extent);
+                bin_boxes[b] = Aabb::new_enclosing(&bin_boxes[b], &object_box);
+                bin_counts[b] += 1;
+            }
+
+            // Sweep from the right to get the area and count of every right-hand side.
+            let mut right_areas = vec![0.0; bin_count];
+            let mut right_counts = vec![0usize; bin_count];
+            let mut right_box = Aabb::new();
+            let mut right_count = 0;
+            for i in (1..bin_count).rev() {
+                right_box = Aabb::new_enclosing(&right_box, &bin_boxes[i]);
+                right_count += bin_counts[i];
+                right_areas[i] = right_box.surface_area();
+                right_counts[i] = right_count;
+            }
+
+            let mut left_box = Aabb::new();
+            let mut left_count = 0;
+            for split in 0..(bin_count - 1) {
+                left_box = Aabb::new_enclosing(&left_box, &bin_boxes[split]);
+                left_count += bin_counts[split];
+                let right_count = right_counts[split + 1];
+                if left_count == 0 || right_count == 0 {
+                    continue;
+                }
+
+                let cost = self.traversal_cost
+                    + self.intersection_cost
+                        * (left_box.surface_area() * left_count as f64
+                            + right_areas[split + 1] * right_count as f64)
+                        / parent_area;
+
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, split));
+                }
+            }
+        }
+
+        best
+    }
+
+    fn bin_index(&self, centroid: f64, extent: &Interval) -> usize {
+        let bin_count = self.bin_count.max(2);
+        let b = ((centroid - extent.min) / extent.size() * bin_count as f64) as usize;
+        b.min(bin_count - 1)
+    }
+
+    fn split_median(objects: &mut Vec<Hittable>, axis: usize) -> Vec<Hittable> {
+        objects.sort_by(|a, b| BvhNode::box_compare(a, b, axis));
+        objects.split_off(objects.len() / 2)
+    }
+
+    fn make_leaf(objects: Vec<Hittable>) -> Hittable {
+        Hittable::HittableList(Box::new(HittableList::new_from(objects)))
+    }
+
+    fn bounded_area(node: &Hittable) -> f64 {
+        // Surface area of the largest finite box at or below `node`.
+        let bbox = node.bounding_box();
+        if bbox.is_bounded() {
+            return bbox.surface_area();
+        }
+        match node {
+            Hittable::Bvh(b) => f64::max(Self::bounded_area(&b.left), Self::bounded_area(&b.right)),
+            _ => 0.0,
+        }
+    }
+
+    fn collect_stats(&self, node: &Hittable, depth: usize, root_area: f64, stats: &mut BvhStats) {
+        // Nodes with unbounded boxes are visited by every ray.
+        let node_box = node.bounding_box();
+        let area_ratio = if root_area > 0.0 && node_box.is_bounded() {
+            node_box.surface_area() / root_area
+        } else {
+            1.0
+        };
+
+        match node {
+            Hittable::Bvh(b) => {
+                stats.node_count += 1;
+                stats.depth = stats.depth.max(depth);
+                stats.sah_cost += area_ratio * self.traversal_cost;
+                self.collect_stats(&b.left, depth + 1, root_area, stats);
+                self.collect_stats(&b.right, depth + 1, root_area, stats);
+            }
+            leaf => {
+                let primitives = match leaf {
+                    Hittable::HittableList(h) => h.len(),
+                    _ => 1,
+                };
+                if primitives == 0 {
+                    return;
+                }
+                stats.leaf_count += 1;
+                stats.primitive_count += primitives;
+                stats.sah_cost += area_ratio * primitives as f64 * self.intersection_cost;
+            }
+        }
+    }
+}
+
+impl Default for BvhBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Shape and estimated traversal cost of a built hierarchy.
+#[derive(Clone, Copy, Debug)]
+pub struct BvhStats {
+    pub depth: usize,
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub primitive_count: usize,
+    pub sah_cost: f64,
+}
+
+impl BvhStats {
+    pub fn new() -> Self {
+        BvhStats {
+            depth: 0,
+            node_count: 0,
+            leaf_count: 0,
+            primitive_count: 0,
+            sah_cost: 0.0,
+        }
+    }
+}
+
+impl Default for BvhStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "depth {}, {} nodes, {} leaves, {} primitives, estimated cost {:.3}",
+            self.depth, self.node_count, self.leaf_count, self.primitive_count, self.sah_cost
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{DiffuseLight, Lambertian, Material};
+    use crate::plane::Plane;
+    use crate::rtweekend::{random_double, seed_random};
+    use crate::sphere::Sphere;
+    use crate::vec3::*;
+    use std::sync::Arc;
+
+    fn random_spheres(count: usize) -> HittableList {
+        seed_random(1);
+        let mut list = HittableList::new();
+        for _ in 0..count {
+            let center = Point3::new_use(
+                10.0 * random_double() - 5.0,
+                10.0 * random_double() - 5.0,
+                10.0 * random_double() - 5.0,
+            );
+            let mat = Material::Lambertian(Arc::new(Lambertian::new(Color::random())));
+            let radius = 0.1 + 0.5 * random_double();
+            list.add(Hittable::Sphere(Box::new(Sphere::new_use(
+                center, radius, mat,
+            ))));
+        }
+        list
+    }
+
+    fn random_rays(count: usize, target: &Aabb) -> Vec<Ray> {
+        // Rays from all around the scene towards random points inside its bounds.
+        seed_random(2);
+        let random_in = |i: &Interval| i.min + (i.max - i.min) * random_double();
+        (0..count)
+            .map(|_| {
+                let origin = Point3::new_use(
+                    16.0 * random_double() - 8.0,
+                    16.0 * random_double() - 8.0,
+                    16.0 * random_double() - 8.0,
+                );
+                let point = Point3::new_use(
+                    random_in(target.axis_interval(0)),
+                    random_in(target.axis_interval(1)),
+                    random_in(target.axis_interval(2)),
+                );
+                Ray::new_use(&origin, &(point - origin))
+            })
+            .collect()
+    }
+
+    fn assert_same_hits(expected: &Hittable, actual: &Hittable) {
+        let mut hits = 0;
+        let target = match expected.bounding_box() {
+            bbox if bbox.is_bounded() => bbox,
+            _ => Aabb::new_from_points(
+                Point3::new_use(-5.0, -5.0, -5.0),
+                Point3::new_use(5.0, 5.0, 5.0),
+            ),
+        };
+        for r in random_rays(2000, &target) {
+            let (mut a, mut b) = (HitRecord::new(), HitRecord::new());
+            let ray_t = Interval::new_use(0.001, f64::INFINITY);
+            let hit = expected.hit(r, ray_t, &mut a);
+            assert_eq!(hit, actual.hit(r, ray_t, &mut b));
+            if hit {
+                hits += 1;
+                assert_eq!(a.t, b.t);
+                assert_eq!((a.p - b.p).length(), 0.0);
+            }
+        }
+        assert!(hits > 20, "only {} of the rays hit anything", hits);
+    }
+
+    #[test]
+    fn bvh_hits_match_a_flat_list() {
+        for count in [1, 2, 3, 50] {
+            let list = random_spheres(count);
+            let bvh = list.clone().into_bvh();
+            assert_same_hits(&Hittable::HittableList(Box::new(list)), &bvh);
+        }
+    }
+
+    #[test]
+    fn single_object_bvh_holds_it_once() {
+        let mut list = HittableList::new();
+        let light =
+            Material::DiffuseLight(Arc::new(DiffuseLight::new(Color::new_use(1.0, 1.0, 1.0))));
+        list.add(Hittable::Sphere(Box::new(Sphere::new_use(
+            Point3::new(),
+            1.0,
+            light,
+        ))));
+        let mut world = HittableList::new();
+        world.add(list.into_bvh());
+        assert_eq!(world.lights().len(), 1);
+    }
+
+    fn builder(split_method: SplitMethod, max_leaf_size: usize) -> BvhBuilder {
+        let mut builder = BvhBuilder::new();
+        builder.split_method = split_method;
+        builder.max_leaf_size = max_leaf_size;
+        builder
+    }
+
+    #[test]
+    fn every_split_method_matches_a_flat_list() {
+        for method in [SplitMethod::Median, SplitMethod::Midpoint, SplitMethod::Sah] {
+            for max_leaf_size in [1, 4] {
+                for count in [1, 2, 7, 200] {
+                    let list = random_spheres(count);
+                    let bvh = builder(method, max_leaf_size).build(list.clone());
+                    assert_same_hits(&Hittable::HittableList(Box::new(list)), &bvh);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn coincident_centroids_still_split() {
+        // No binned split exists, so SAH falls back to the median.
+        let mut list = HittableList::new();
+        for k in 0..10 {
+            let mat =
+                Material::Lambertian(Arc::new(Lambertian::new(Color::new_use(0.5, 0.5, 0.5))));
+            let radius = 0.5 + 0.1 * k as f64;
+            list.add(Hittable::Sphere(Box::new(Sphere::new_use(
+                Point3::new(),
+                radius,
+                mat,
+            ))));
+        }
+        let sah = builder(SplitMethod::Sah, 4);
+        let bvh = sah.build(list.clone());
+        assert!(sah.stats(&bvh).leaf_count > 1);
+        assert_same_hits(&Hittable::HittableList(Box::new(list)), &bvh);
+    }
+
+    #[test]
+    fn planes_go_into_the_unbounded_leaf() {
+        let mut list = random_spheres(20);
+        let mat = Material::Lambertian(Arc::new(Lambertian::new(Color::new_use(0.5, 0.5, 0.5))));
+        list.add(Hittable::Plane(Box::new(Plane::new_use(
+            Point3::new_use(0.0, -6.0, 0.0),
+            Vec3::new_use(0.2, 1.0, 0.1),
+            mat,
+        ))));
+
+        for method in [SplitMethod::Median, SplitMethod::Midpoint, SplitMethod::Sah] {
+            let bvh = builder(method, 4).build(list.clone());
+            let [bounded, unbounded] = match &bvh {
+                Hittable::Bvh(root) => root.children(),
+                _ => panic!("the root should be a BVH node"),
+            };
+            assert!(bounded.bounding_box().is_bounded());
+            match unbounded {
+                Hittable::HittableList(leaf) => {
+                    let objects = leaf.clone().into_objects();
+                    assert_eq!(objects.len(), 1);
+                    assert!(matches!(objects[0], Hittable::Plane(_)));
+                }
+                _ => panic!("the plane should sit in a leaf list next to the tree"),
+            }
+            assert_same_hits(&Hittable::HittableList(Box::new(list.clone())), &bvh);
+        }
+    }
+
+    #[test]
+    fn stats_count_every_primitive_once() {
+        for method in [SplitMethod::Median, SplitMethod::Midpoint, SplitMethod::Sah] {
+            let b = builder(method, 4);
+            let stats = b.stats(&b.build(random_spheres(100)));
+            assert_eq!(stats.primitive_count, 100);
+            assert!(stats.leaf_count >= 25);
+            assert!(stats.node_count >= stats.leaf_count - 1);
+            assert!(stats.depth >= 5 && stats.depth < 100);
+            assert!(stats.sah_cost > 0.0);
+        }
+
+        // A list small enough for one leaf still gets a root node.
+        let single = builder(SplitMethod::Median, 4);
+        let stats = single.stats(&single.build(random_spheres(3)));
+        assert_eq!(
+            (stats.node_count, stats.leaf_count, stats.primitive_count),
+            (1, 1, 3)
+        );
+        assert_eq!(stats.depth, 1);
+    }
+}
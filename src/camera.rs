@@ -1,27 +1,80 @@
 #![allow(dead_code)]
 
+use crate::filter::Filter;
 use crate::hittable::*;
+use crate::image::Image;
 use crate::interval::*;
 use crate::ray::*;
 use crate::rtweekend::degrees_to_radians;
-use crate::rtweekend::random_double;
+use crate::rtweekend::hash_u64;
+use crate::sampler::{Sampler, SamplerType};
 use crate::vec3::*;
 
-use core::f64::INFINITY;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+// What a ray sees when it escapes the scene.
+#[derive(Clone, Copy)]
+pub enum Background {
+    // Black; the scene is lit only by emissive materials.
+    None,
+    Solid(Color),
+    // Blend from the first color (looking straight down) to the second (straight up).
+    Gradient(Color, Color),
+}
+
+// How the radiance along each camera ray is estimated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Integrator {
+    // Path tracing that samples the lights directly at every diffuse bounce and combines that
+    // with BSDF sampling by multiple importance sampling.
+    Path,
+    // Path tracing that only finds lights when a scattered ray happens to hit them.
+    Bsdf,
+    // Shading normals mapped to colors, for debugging geometry.
+    Normals,
+}
+
+impl Integrator {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "path" => Some(Integrator::Path),
+            "bsdf" => Some(Integrator::Bsdf),
+            "normals" => Some(Integrator::Normals),
+            _ => None,
+        }
+    }
+}
 
 pub struct Camera {
     pub aspect_ratio: f64,
     pub image_width: i32,
-    pub samples_per_pixel: i32,
+    pub samples_per_pixel: i32, // The most samples a pixel takes when sampling adaptively
+    pub adaptive_threshold: f64, // Relative noise at which a pixel stops; 0 samples every pixel fully
+    pub adaptive_min_samples: i32, // Samples a pixel takes before it may stop, up to samples_per_pixel
     pub max_depth: i32,
+    pub roulette_depth: i32, // Bounces before Russian roulette may end a path
     pub vfov: f64,
     pub lookfrom: Point3,
     pub lookat: Point3,
     pub vup: Vec3,
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    pub shutter_open: f64, // Time the shutter opens; rays get random times until it closes
+    pub shutter_close: f64, // Equal to `shutter_open` for a still frame
+    pub background: Background,
+    pub integrator: Integrator,
+    pub sampler: SamplerType,
+    pub filter: Filter,
+    pub filter_radius: f64, // Pixels; 0 uses the filter's default radius
+    pub seed: u64,
+    pub thread_count: usize, // Worker threads; 0 uses every available core
+    pub tile_size: i32,
     image_height: i32,
-    pixel_samples_scale: f64,
+    filter_extent: f64,      // Effective filter radius
+    sample_counts: Vec<u32>, // Samples taken by each pixel in the last render
     center: Point3,
     pixel00_loc: Point3,
     pixel_delta_u: Vec3,
@@ -35,24 +88,112 @@ pub struct Camera {
 
 impl Camera {
     // Public Camera Paraeters
-    pub fn render(&mut self, world: Hittable) {
+    pub fn render(&mut self, world: Hittable, lights: HittableList) -> Image {
+        // `lights` holds the emitters to sample directly, see `HittableList::lights`.
         Self::initialize(self);
+        let start = Instant::now();
+        let lights = match self.integrator {
+            Integrator::Path => lights,
+            _ => HittableList::new(),
+        };
 
-        print!("P3\n{} {}\n255\n", self.image_width, self.image_height);
+        let thread_count = if self.thread_count > 0 {
+            self.thread_count
+        } else {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        };
+        let tiles_x = (self.image_width + self.tile_size - 1) / self.tile_size;
+        let tiles_y = (self.image_height + self.tile_size - 1) / self.tile_size;
+        let tile_count = (tiles_x * tiles_y) as usize;
+
+        let pixel_count = (self.image_width * self.image_height) as usize;
+        let mut sums = vec![Color::new(); pixel_count];
+        let mut weights = vec![0.0; pixel_count];
+        let mut counts = vec![0; pixel_count];
+        let next_tile = AtomicUsize::new(0);
+        let (tx, rx) = mpsc::channel();
+        let cam: &Camera = self;
+
+        thread::scope(|s| {
+            for _ in 0..thread_count {
+                let tx = tx.clone();
+                let world = &world;
+                let lights = &lights;
+                let next_tile = &next_tile;
+                s.spawn(move || loop {
+                    let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+                    if tile >= tile_count {
+                        break;
+                    }
+                    let buffer = cam.render_tile(tile, tiles_x, world, lights);
+                    if tx.send((tile, buffer)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(tx);
 
-        for j in 0..(self.image_height) {
-            eprintln!("\rScanlines remaining: {}", self.image_height - j);
-            for i in 0..(self.image_width) {
-                let mut pixel_color = Color::new();
-                for _s in 0..(self.samples_per_pixel) {
-                    let r = self.get_ray(i, j);
-                    pixel_color += Self::ray_color(&r, self.max_depth, world.clone());
+            // Tiles overlap where their samples spread into neighbouring pixels. They're added up
+            // in tile order, whichever finishes first, so the result doesn't depend on threading.
+            let mut pending: Vec<Option<TileBuffer>> = (0..tile_count).map(|_| None).collect();
+            let mut next_to_merge = 0;
+            for (done, (tile, buffer)) in rx.iter().enumerate() {
+                eprint!("\rTiles remaining: {:<8}", tile_count - done - 1);
+                pending[tile] = Some(buffer);
+                while let Some(buffer) = pending.get_mut(next_to_merge).and_then(Option::take) {
+                    buffer.add_to(&mut sums, &mut weights, &mut counts, cam.image_width);
+                    next_to_merge += 1;
                 }
-                // eprintln!("Pixel Color: {}\t", pixel_color);
-                write_color(pixel_color);
             }
+        });
+
+        let mut image = Image::new(self.image_width as usize, self.image_height as usize);
+        for (k, (sum, weight)) in sums.iter().zip(&weights).enumerate() {
+            // Filters with negative lobes can push a pixel below zero next to a bright edge.
+            let pixel_color = if *weight > 0.0 {
+                let c = *sum / *weight;
+                Color::new_use(c.x().max(0.0), c.y().max(0.0), c.z().max(0.0))
+            } else {
+                Color::new()
+            };
+            let i = k % self.image_width as usize;
+            let j = k / self.image_width as usize;
+            image.set_pixel(i, j, pixel_color);
+        }
+
+        let seconds = start.elapsed().as_secs_f64();
+        let samples = counts.iter().map(|&n| n as f64).sum::<f64>();
+        eprintln!(
+            "\rDone in {:.2}s ({:.0} samples/s, {:.1} per pixel).",
+            seconds,
+            samples / seconds,
+            samples / pixel_count as f64
+        );
+        self.sample_counts = counts;
+        image
+    }
+
+    pub fn sample_map(&self) -> Image {
+        // Samples each pixel took in the last render, as a fraction of `samples_per_pixel`: white
+        // where a pixel used its whole budget.
+        self.sample_image(1.0 / self.samples_per_pixel as f64)
+    }
+
+    pub fn sample_counts(&self) -> Image {
+        // Samples each pixel took in the last render, as the raw count. Half float EXR stores
+        // counts exactly up to 2048.
+        self.sample_image(1.0)
+    }
+
+    fn sample_image(&self, scale: f64) -> Image {
+        let mut map = Image::new(self.image_width as usize, self.image_height as usize);
+        for (k, &n) in self.sample_counts.iter().enumerate() {
+            let value = n as f64 * scale;
+            let i = k % self.image_width as usize;
+            let j = k / self.image_width as usize;
+            map.set_pixel(i, j, Color::new_use(value, value, value));
         }
-        eprintln!("\rDone.              ");
+        map
     }
 
     pub fn new() -> Self {
@@ -60,15 +201,32 @@ impl Camera {
             aspect_ratio: 1.0,
             image_width: 100,
             samples_per_pixel: 10,
+            adaptive_threshold: 0.0,
+            adaptive_min_samples: 64,
             max_depth: 10,
+            roulette_depth: 3,
             vfov: 90.0,
             lookfrom: Point3::new_use(0.0, 0.0, 0.0),
             lookat: Point3::new_use(0.0, 0.0, -1.0),
             vup: Vec3::new_use(0.0, 1.0, 0.0),
             defocus_angle: 0.0,
             focus_dist: 10.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            background: Background::Gradient(
+                Color::new_use(1.0, 1.0, 1.0),
+                Color::new_use(0.5, 0.7, 1.0),
+            ),
+            integrator: Integrator::Path,
+            sampler: SamplerType::Independent,
+            filter: Filter::Box,
+            filter_radius: 0.0,
+            seed: 0,
+            thread_count: 0,
+            tile_size: 16,
             image_height: 0,
-            pixel_samples_scale: 0.0,
+            filter_extent: 0.0,
+            sample_counts: Vec::new(),
             center: Point3::new(),
             pixel00_loc: Point3::new(),
             pixel_delta_u: Vec3::new(),
@@ -87,8 +245,13 @@ impl Camera {
         if self.image_height < 1 {
             self.image_height = 1;
         }
+        self.tile_size = self.tile_size.max(1);
 
-        self.pixel_samples_scale = 1.0 / self.samples_per_pixel as f64;
+        self.filter_extent = if self.filter_radius > 0.0 {
+            self.filter_radius
+        } else {
+            self.filter.default_radius()
+        };
 
         self.center = self.lookfrom;
 
@@ -123,52 +286,570 @@ impl Camera {
         self.defocus_disk_v = self.v * defocus_radius;
     }
 
-    fn get_ray(&self, i: i32, j: i32) -> Ray {
-        // Construct a camera ray originiating from the defocus disk and directed at a randomly sampled
-        // point around the pixel location i, j.
+    fn tile_bounds(&self, tile: usize, tiles_x: i32) -> (i32, i32, i32, i32) {
+        // Returns the pixel range [x0, x1) x [y0, y1) covered by a tile.
+        let x0 = (tile as i32 % tiles_x) * self.tile_size;
+        let y0 = (tile as i32 / tiles_x) * self.tile_size;
+        let x1 = i32::min(x0 + self.tile_size, self.image_width);
+        let y1 = i32::min(y0 + self.tile_size, self.image_height);
+        (x0, y0, x1, y1)
+    }
+
+    fn render_tile(
+        &self,
+        tile: usize,
+        tiles_x: i32,
+        world: &Hittable,
+        lights: &HittableList,
+    ) -> TileBuffer {
+        let (x0, y0, x1, y1) = self.tile_bounds(tile, tiles_x);
+        let mut buffer = TileBuffer::new(self, x0, y0, x1, y1);
+        let mut sampler = Sampler::new(self.sampler, self.samples_per_pixel);
+        for j in y0..y1 {
+            for i in x0..x1 {
+                let pixel_seed = hash_u64(self.seed ^ hash_u64((j * self.image_width + i) as u64));
+                let mut noise = PixelNoise::new();
+                let min_samples = self.adaptive_min_samples();
+                let check_interval = self.adaptive_check_interval();
+                for s in 0..(self.samples_per_pixel) {
+                    // Every sample's numbers depend only on seed, pixel and sample number, so
+                    // images don't depend on threading or tile layout.
+                    sampler.start_pixel_sample(pixel_seed, s);
+                    let offset = sampler.get_2d();
+                    let x = i as f64 + offset[0];
+                    let y = j as f64 + offset[1];
+                    let r = self.get_ray(x, y, &mut sampler);
+                    let sample_color = match self.integrator {
+                        Integrator::Path | Integrator::Bsdf => {
+                            self.ray_color(&r, world, lights, &mut sampler)
+                        }
+                        Integrator::Normals => Self::normal_color(&r, world),
+                    };
+                    buffer.splat(self, x, y, sample_color);
+
+                    noise.add(sample_color);
+                    if self.adaptive_threshold > 0.0
+                        && noise.count >= min_samples
+                        && (noise.count - min_samples).is_multiple_of(check_interval)
+                        && noise.relative_error() <= self.adaptive_threshold
+                    {
+                        break;
+                    }
+                }
+                buffer.set_count(i, j, noise.count);
+            }
+        }
+        buffer
+    }
+
+    fn adaptive_min_samples(&self) -> u32 {
+        // A budget below the minimum is spent in full.
+        self.adaptive_min_samples
+            .clamp(1, self.samples_per_pixel.max(1)) as u32
+    }
+
+    fn adaptive_check_interval(&self) -> u32 {
+        // Past the minimum, pixels check their noise again every quarter of it.
+        (self.adaptive_min_samples() / 4).max(1)
+    }
+
+    fn get_ray(&self, x: f64, y: f64, sampler: &mut Sampler) -> Ray {
+        // Construct a camera ray originiating from the defocus disk and directed at the point
+        // (x, y) of the image, measured in pixels from its top-left corner.
 
-        let offset = Self::sample_square();
-        let pixel_sample = self.pixel00_loc
-            + ((i as f64 + offset.x()) * self.pixel_delta_u)
-            + ((j as f64 + offset.y()) * self.pixel_delta_v);
+        let pixel_sample =
+            self.pixel00_loc + ((x - 0.5) * self.pixel_delta_u) + ((y - 0.5) * self.pixel_delta_v);
 
         let ray_origin = if self.defocus_angle <= 0.0 {
             self.center
         } else {
-            Self::defocus_disk_sample(&self)
+            self.defocus_disk_sample(sampler.get_2d())
         };
         let ray_direction = pixel_sample - ray_origin;
 
-        Ray::new_use(&ray_origin, &ray_direction)
-    }
+        // Only draw a time when the shutter stays open, so still frames keep their random sequence.
+        let ray_time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + (self.shutter_close - self.shutter_open) * sampler.get_1d()
+        } else {
+            self.shutter_open
+        };
 
-    fn sample_square() -> Vec3 {
-        Vec3::new_use(random_double() - 0.5, random_double() - 0.5, 0.0)
+        Ray::new_with_time(&ray_origin, &ray_direction, ray_time)
     }
 
-    fn defocus_disk_sample(&self) -> Point3 {
-        let p = random_in_unit_disk();
+    fn defocus_disk_sample(&self, u: [f64; 2]) -> Point3 {
+        let p = sample_in_unit_disk(u);
         self.center + (p[0] * self.defocus_disk_u) + (p[1] * self.defocus_disk_v)
     }
 
-    fn ray_color(r: &Ray, depth: i32, world: Hittable) -> Color {
-        if depth <= 0 {
-            return Color::new_use(0.0, 0.0, 0.0);
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &Hittable,
+        lights: &HittableList,
+        sampler: &mut Sampler,
+    ) -> Color {
+        let mut color = Color::new();
+        let mut throughput = Color::new_use(1.0, 1.0, 1.0);
+        let mut ray = *r;
+        // Density with which the previous bounce sampled `ray`, or None for camera rays and delta
+        // bounces, which light sampling can't produce.
+        let mut bsdf_pdf: Option<f64> = None;
+
+        for depth in 0..self.max_depth {
+            let mut rec: HitRecord = HitRecord::new();
+
+            // If the ray hits nothing, add the background color.
+            if !world.hit(ray, Interval::new_use(0.001, f64::INFINITY), &mut rec) {
+                color += throughput * self.background_color(&ray);
+                break;
+            }
+
+            let mut color_from_emission = rec.material().emitted(rec.u, rec.v, &rec.p);
+
+            // Light sampling at the previous bounce may also have found this emitter, so the two
+            // estimates are weighted to add up to one.
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                if rec.material().is_emissive() {
+                    let light_pdf = lights.pdf_value(*ray.origin(), *ray.direction(), ray.time());
+                    color_from_emission *= power_heuristic(bsdf_pdf, light_pdf);
+                }
+            }
+            color += throughput * color_from_emission;
+
+            // Every bounce takes the same sample dimensions whichever branches it follows, so
+            // each dimension keeps a single meaning across the pixel's samples.
+            let light_u = if lights.is_empty() {
+                None
+            } else {
+                Some(sampler.get_2d())
+            };
+            let uc = sampler.get_1d();
+            let u = sampler.get_2d();
+            let roulette_u = sampler.get_1d();
+
+            let sample = rec.material().sample(&ray, &rec, uc, u);
+
+            // Next-event estimation: sample a direction towards the lights and add whatever
+            // emitter it reaches first. This doesn't depend on whether the material sample was
+            // absorbed.
+            if let Some(light_u) = light_u {
+                if !rec.material().is_delta() {
+                    color += throughput * self.sample_lights(&ray, &rec, world, lights, light_u);
+                }
+            }
+
+            let Some(sample) = sample else {
+                break;
+            };
+            throughput = throughput * sample.weight;
+            bsdf_pdf = if sample.is_delta {
+                None
+            } else {
+                Some(sample.pdf)
+            };
+            ray = sample.ray;
+
+            // Russian roulette: end dim paths at random, and scale up the ones that survive so
+            // the estimate stays unbiased.
+            if depth + 1 >= self.roulette_depth {
+                let survival = throughput.x().max(throughput.y()).max(throughput.z());
+                if survival < 1.0 {
+                    if roulette_u >= survival {
+                        break;
+                    }
+                    throughput /= survival;
+                }
+            }
         }
 
+        color
+    }
+
+    fn sample_lights(
+        &self,
+        r: &Ray,
+        rec: &HitRecord,
+        world: &Hittable,
+        lights: &HittableList,
+        u: [f64; 2],
+    ) -> Color {
+        let light_direction = lights.random(rec.p, r.time(), u);
+        let light_pdf = lights.pdf_value(rec.p, light_direction, r.time());
+        let scattering_pdf = rec.material().pdf(r, rec, light_direction);
+        if light_pdf <= 0.0 || scattering_pdf <= 0.0 {
+            return Color::new();
+        }
+
+        let light_ray = Ray::new_with_time(&rec.p, &light_direction, r.time());
+        let mut light_rec = HitRecord::new();
+        if !world.hit(
+            light_ray,
+            Interval::new_use(0.001, f64::INFINITY),
+            &mut light_rec,
+        ) {
+            return Color::new();
+        }
+        let emitted = light_rec
+            .material()
+            .emitted(light_rec.u, light_rec.v, &light_rec.p);
+        let weight = power_heuristic(light_pdf, scattering_pdf);
+        (weight / light_pdf) * rec.material().eval(r, rec, light_direction) * emitted
+    }
+
+    fn normal_color(r: &Ray, world: &Hittable) -> Color {
         let mut rec: HitRecord = HitRecord::new();
 
-        if world.hit(*r, Interval::new_use(0.001, INFINITY), &mut rec) {
-            let mut scattered = Ray::new();
-            let mut attenuation = Color::new();
-            if rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
-                return attenuation * Self::ray_color(&scattered, depth - 1, world);
-            }
+        if !world.hit(*r, Interval::new_use(0.001, f64::INFINITY), &mut rec) {
             return Color::new_use(0.0, 0.0, 0.0);
         }
+        0.5 * (rec.normal + Color::new_use(1.0, 1.0, 1.0))
+    }
+
+    fn background_color(&self, r: &Ray) -> Color {
+        match self.background {
+            Background::None => Color::new_use(0.0, 0.0, 0.0),
+            Background::Solid(color) => color,
+            Background::Gradient(bottom, top) => {
+                let unit_direction = unit_vector(*r.direction());
+                let a = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - a) * bottom + a * top
+            }
+        }
+    }
+}
+
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    // MIS weight for a sample drawn with density `pdf` when `other_pdf` could also have drawn it.
+    let p2 = pdf * pdf;
+    let sum = p2 + other_pdf * other_pdf;
+    if sum > 0.0 {
+        p2 / sum
+    } else {
+        0.0
+    }
+}
+
+// Filtered samples of one tile: weighted color sums and filter weights for the tile's pixels and
+// the border of neighbours its samples reach.
+struct TileBuffer {
+    x0: i32,
+    y0: i32,
+    width: i32,
+    height: i32,
+    sums: Vec<Color>,
+    weights: Vec<f64>,
+    counts: Vec<u32>, // Samples taken by each of the tile's own pixels; zero in the border
+}
+
+impl TileBuffer {
+    fn new(cam: &Camera, x0: i32, y0: i32, x1: i32, y1: i32) -> Self {
+        let border = (cam.filter_extent + 0.5).ceil() as i32;
+        let (x0, y0) = (i32::max(x0 - border, 0), i32::max(y0 - border, 0));
+        let x1 = i32::min(x1 + border, cam.image_width);
+        let y1 = i32::min(y1 + border, cam.image_height);
+        let size = ((x1 - x0) * (y1 - y0)) as usize;
+        TileBuffer {
+            x0,
+            y0,
+            width: x1 - x0,
+            height: y1 - y0,
+            sums: vec![Color::new(); size],
+            weights: vec![0.0; size],
+            counts: vec![0; size],
+        }
+    }
+
+    fn set_count(&mut self, i: i32, j: i32, count: u32) {
+        self.counts[((j - self.y0) * self.width + (i - self.x0)) as usize] = count;
+    }
+
+    fn splat(&mut self, cam: &Camera, x: f64, y: f64, sample_color: Color) {
+        // Adds a sample taken at image position (x, y) to every pixel whose center (at half-pixel
+        // offsets) is within the filter's reach.
+        let radius = cam.filter_extent;
+        let i0 = i32::max((x - 0.5 - radius).ceil() as i32, self.x0);
+        let i1 = i32::min((x - 0.5 + radius).floor() as i32, self.x0 + self.width - 1);
+        let j0 = i32::max((y - 0.5 - radius).ceil() as i32, self.y0);
+        let j1 = i32::min((y - 0.5 + radius).floor() as i32, self.y0 + self.height - 1);
+        for j in j0..=j1 {
+            for i in i0..=i1 {
+                let weight = cam
+                    .filter
+                    .evaluate(i as f64 + 0.5 - x, j as f64 + 0.5 - y, radius);
+                if weight != 0.0 {
+                    let k = ((j - self.y0) * self.width + (i - self.x0)) as usize;
+                    self.sums[k] += weight * sample_color;
+                    self.weights[k] += weight;
+                }
+            }
+        }
+    }
+
+    fn add_to(
+        &self,
+        sums: &mut [Color],
+        weights: &mut [f64],
+        counts: &mut [u32],
+        image_width: i32,
+    ) {
+        for j in 0..self.height {
+            for i in 0..self.width {
+                let k = (j * self.width + i) as usize;
+                let target = ((self.y0 + j) * image_width + self.x0 + i) as usize;
+                sums[target] += self.sums[k];
+                weights[target] += self.weights[k];
+                counts[target] += self.counts[k];
+            }
+        }
+    }
+}
+
+// Running mean and variance (Welford's method) of a pixel's sample luminance.
+struct PixelNoise {
+    count: u32,
+    mean: f64,
+    m2: f64, // Sum of squared differences from the mean
+}
+
+impl PixelNoise {
+    fn new() -> Self {
+        PixelNoise {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    fn add(&mut self, sample_color: Color) {
+        let luminance =
+            0.2126 * sample_color.x() + 0.7152 * sample_color.y() + 0.0722 * sample_color.z();
+        self.count += 1;
+        let delta = luminance - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    fn relative_error(&self) -> f64 {
+        // Half-width of the 95% confidence interval of the mean, relative to the mean. Very dark
+        // pixels are measured against a floor instead, so they don't chase invisible noise.
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let variance = self.m2 / (self.count - 1) as f64;
+        let half_width = 1.96 * (variance / self.count as f64).sqrt();
+        half_width / f64::max(self.mean, 1e-3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::BvhBuilder;
+    use crate::constant_medium::ConstantMedium;
+    use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+    use crate::quad::Quad;
+    use crate::sphere::Sphere;
+    use std::sync::Arc;
+
+    fn test_world() -> HittableList {
+        // Every kind of scattering, so every sampling dimension of a path gets used.
+        let mut world = HittableList::new();
+        let sphere = |center: Point3, radius: f64, mat: Material| {
+            Hittable::Sphere(Box::new(Sphere::new_use(center, radius, mat)))
+        };
+        let ground = Material::Lambertian(Arc::new(Lambertian::new(Color::new_use(0.5, 0.5, 0.5))));
+        world.add(sphere(Point3::new_use(0.0, -100.5, -1.0), 100.0, ground));
+        let glass = Material::Dielectric(Arc::new(Dielectric::new(1.5)));
+        world.add(sphere(Point3::new_use(-1.0, 0.0, -1.0), 0.5, glass));
+        let metal = Material::Metal(Arc::new(Metal::new(Color::new_use(0.8, 0.6, 0.2), 0.3)));
+        world.add(sphere(Point3::new_use(1.0, 0.0, -1.0), 0.5, metal));
+        let fog = sphere(Point3::new_use(0.0, 0.0, -1.2), 0.5, Material::new());
+        world.add(Hittable::ConstantMedium(Box::new(ConstantMedium::new_use(
+            fog,
+            2.0,
+            Color::new_use(0.9, 0.9, 0.9),
+        ))));
+        let light =
+            Material::DiffuseLight(Arc::new(DiffuseLight::new(Color::new_use(4.0, 4.0, 4.0))));
+        world.add(Hittable::Quad(Box::new(Quad::new_use(
+            Point3::new_use(-1.0, 1.5, -2.0),
+            Vec3::new_use(2.0, 0.0, 0.0),
+            Vec3::new_use(0.0, 0.0, 1.0),
+            light,
+        ))));
+        world
+    }
+
+    fn render(configure: impl Fn(&mut Camera)) -> Image {
+        let world = test_world();
+        let lights = world.lights();
+        let mut cam = Camera::new();
+        cam.image_width = 24;
+        cam.aspect_ratio = 1.5;
+        cam.samples_per_pixel = 8;
+        cam.max_depth = 8;
+        cam.roulette_depth = 1;
+        cam.seed = 7;
+        cam.background =
+            Background::Gradient(Color::new_use(1.0, 1.0, 1.0), Color::new_use(0.5, 0.7, 1.0));
+        configure(&mut cam);
+        cam.render(BvhBuilder::new().build(world), lights)
+    }
+
+    fn bits(image: &Image) -> Vec<[u64; 3]> {
+        image
+            .pixels()
+            .iter()
+            .map(|p| [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()])
+            .collect()
+    }
+
+    #[test]
+    fn renders_are_independent_of_threads() {
+        for sampler in [
+            SamplerType::Independent,
+            SamplerType::Stratified,
+            SamplerType::Halton,
+            SamplerType::Sobol,
+        ] {
+            for filter in [Filter::Box, Filter::Mitchell] {
+                for tile_size in [5, 16] {
+                    let render_with = |threads: usize| {
+                        render(|cam| {
+                            cam.sampler = sampler;
+                            cam.filter = filter;
+                            cam.tile_size = tile_size;
+                            cam.thread_count = threads;
+                        })
+                    };
+                    let reference = bits(&render_with(1));
+                    for threads in [2, 7] {
+                        assert!(
+                            bits(&render_with(threads)) == reference,
+                            "{:?} sampler, {:?} filter, {} pixel tiles: {} threads differ from 1",
+                            sampler,
+                            filter,
+                            tile_size,
+                            threads
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn box_filtered_renders_are_independent_of_tiles() {
+        // Wider filters add up samples from neighbouring tiles in an order that depends on the
+        // tiling, which can change the last bits.
+        let reference = bits(&render(|cam| cam.tile_size = 16));
+        for tile_size in [1, 5, 100] {
+            assert!(bits(&render(|cam| cam.tile_size = tile_size)) == reference);
+        }
+    }
+
+    #[test]
+    fn adaptive_renders_are_independent_of_threads() {
+        let configure = |cam: &mut Camera, threads: usize| {
+            cam.samples_per_pixel = 32;
+            cam.adaptive_threshold = 0.3;
+            cam.adaptive_min_samples = 8;
+            cam.sampler = SamplerType::Sobol;
+            cam.thread_count = threads;
+        };
+        let reference = render(|cam| configure(cam, 1));
+        let image = render(|cam| configure(cam, 5));
+        assert!(bits(&image) == bits(&reference));
+    }
+
+    #[test]
+    fn adaptive_pixels_take_the_minimum_before_stopping() {
+        let counts = |spp: i32, min_samples: i32| {
+            let mut cam = Camera::new();
+            cam.image_width = 24;
+            cam.samples_per_pixel = spp;
+            cam.adaptive_threshold = 0.5;
+            cam.adaptive_min_samples = min_samples;
+            let world = test_world();
+            let lights = world.lights();
+            cam.render(BvhBuilder::new().build(world), lights);
+            cam.sample_counts
+        };
+
+        // Past the minimum of 8, pixels check every 2 samples.
+        let sample_counts = counts(32, 8);
+        assert!(sample_counts
+            .iter()
+            .all(|&n| (8..=32).contains(&n) && n % 2 == 0));
+        assert!(sample_counts.iter().sum::<u32>() < 32 * sample_counts.len() as u32);
+
+        // A budget below the minimum is spent in full.
+        assert!(counts(8, 64).iter().all(|&n| n == 8));
+    }
+
+    #[test]
+    fn seeds_change_the_render() {
+        let a = render(|cam| cam.thread_count = 2);
+        let b = render(|cam| {
+            cam.thread_count = 2;
+            cam.seed = 8;
+        });
+        assert!(bits(&a) != bits(&b));
+    }
 
-        let unit_direction = unit_vector(*r.direction());
-        let a = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - a) * Color::new_use(1.0, 1.0, 1.0) + a * Color::new_use(0.5, 0.7, 1.0)
+    #[test]
+    fn splatting_a_constant_image_returns_the_constant() {
+        let constant = Color::new_use(0.25, 1.5, 3.0);
+        for filter in [
+            Filter::Box,
+            Filter::Tent,
+            Filter::Gaussian,
+            Filter::Mitchell,
+            Filter::Lanczos,
+        ] {
+            let mut cam = Camera::new();
+            cam.image_width = 12;
+            cam.aspect_ratio = 1.5;
+            cam.filter = filter;
+            cam.initialize();
+            let (width, height) = (cam.image_width, cam.image_height);
+
+            // Two tiles side by side, so their borders overlap when they're merged.
+            let mut tiles = [
+                TileBuffer::new(&cam, 0, 0, width / 2, height),
+                TileBuffer::new(&cam, width / 2, 0, width, height),
+            ];
+            for j in 0..height {
+                for i in 0..width {
+                    let tile = &mut tiles[(i >= width / 2) as usize];
+                    for k in 0..16 {
+                        let x = i as f64 + (k % 4) as f64 / 4.0 + 0.125;
+                        let y = j as f64 + (k / 4) as f64 / 4.0 + 0.125;
+                        tile.splat(&cam, x, y, constant);
+                    }
+                }
+            }
+
+            let size = (width * height) as usize;
+            let mut sums = vec![Color::new(); size];
+            let mut weights = vec![0.0; size];
+            let mut counts = vec![0; size];
+            for tile in &tiles {
+                tile.add_to(&mut sums, &mut weights, &mut counts, width);
+            }
+            for (sum, weight) in sums.iter().zip(&weights) {
+                assert!(*weight > 0.0, "{:?}", filter);
+                let pixel = *sum / *weight;
+                for c in 0..3 {
+                    assert!(
+                        (pixel[c] - constant[c]).abs() < 1e-9 * constant[c],
+                        "{:?} gave {}",
+                        filter,
+                        pixel[c]
+                    );
+                }
+            }
+        }
     }
 }
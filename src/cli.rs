@@ -0,0 +1,340 @@
+#![allow(dead_code)]
+// Command-line options for the renderer binary
+
+use std::path::PathBuf;
+
+use crate::bvh::SplitMethod;
+use crate::camera::Integrator;
+use crate::exr::PixelType;
+use crate::filter::Filter;
+use crate::output::ImageFormat;
+use crate::png::BitDepth;
+use crate::sampler::SamplerType;
+
+pub const USAGE: &str = "\
+Usage: raytracing [OPTIONS] [SCENE]
+
+Renders SCENE (a TOML scene file), or the built-in random spheres scene when none is given.
+Camera options override the values from the scene.
+
+Options:
+  -o, --output FILE       Output image; the format follows the extension
+                          (.ppm, .png, .16.png for 16 bit PNG, .exr, .hdr). Default: PPM on stdout
+  -f, --format FORMAT     ppm, png, png16, exr, exr-float or hdr (overrides the extension)
+  -w, --width N           Image width in pixels
+  -a, --aspect RATIO      Aspect ratio as a number or W:H, e.g. 1.5 or 16:9
+  -s, --spp N             Samples per pixel (the most a pixel takes with --adaptive)
+      --adaptive T        Stop sampling pixels once their relative noise is below T
+      --adaptive-min N    Samples every pixel takes before it may stop (default 64; at most
+                          --spp). Past it, pixels check their noise every N/4 samples
+      --sample-map FILE   Also write an image of the samples taken per pixel, as a fraction of
+                          --spp (white where a pixel took them all)
+      --sample-layer      Add the number of samples taken per pixel to the EXR output as a
+                          samples layer
+  -d, --depth N           Maximum ray bounce depth
+      --roulette-depth N  Bounces before Russian roulette may end a path
+      --vfov DEGREES      Vertical field of view
+      --seed N            Random seed
+  -t, --threads N         Worker threads (0 uses every core)
+  -i, --integrator NAME   path, bsdf or normals
+      --sampler NAME      independent, stratified, halton or sobol
+      --filter NAME       Pixel filter: box, tent, gaussian, mitchell or lanczos
+      --filter-radius R   Filter radius in pixels (default depends on the filter)
+      --bvh METHOD        BVH build: sah, median or midpoint
+      --bvh-stats         Print BVH statistics for every build method
+  -h, --help              Show this help";
+
+#[derive(Clone, Default)]
+pub struct Options {
+    pub scene: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub format: Option<ImageFormat>,
+    pub width: Option<i32>,
+    pub aspect_ratio: Option<f64>,
+    pub samples_per_pixel: Option<i32>,
+    pub adaptive_threshold: Option<f64>,
+    pub adaptive_min_samples: Option<i32>,
+    pub sample_map: Option<PathBuf>,
+    pub sample_layer: bool,
+    pub max_depth: Option<i32>,
+    pub roulette_depth: Option<i32>,
+    pub vfov: Option<f64>,
+    pub seed: Option<u64>,
+    pub threads: Option<usize>,
+    pub integrator: Option<Integrator>,
+    pub sampler: Option<SamplerType>,
+    pub filter: Option<Filter>,
+    pub filter_radius: Option<f64>,
+    pub split_method: Option<SplitMethod>,
+    pub bvh_stats: bool,
+    pub help: bool,
+}
+
+impl Options {
+    pub fn output_format(&self) -> Result<Option<ImageFormat>, String> {
+        // The explicit format wins; otherwise it comes from the output file's extension.
+        match (&self.format, &self.output) {
+            (Some(format), _) => Ok(Some(*format)),
+            (None, Some(path)) => ImageFormat::from_path(path)
+                .map(Some)
+                .ok_or_else(|| format!("can't tell the image format of '{}'", path.display())),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+    // `args` excludes the program name.
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            if options.scene.is_some() {
+                return Err(format!("unexpected argument '{}'", arg));
+            }
+            options.scene = Some(PathBuf::from(arg));
+            continue;
+        }
+
+        // Accept both "--name value" and "--name=value".
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((n, v)) if n.starts_with("--") => (n.to_string(), Some(v.to_string())),
+            _ => (arg.clone(), None),
+        };
+        if inline_value.is_some()
+            && matches!(name.as_str(), "--help" | "--bvh-stats" | "--sample-layer")
+        {
+            return Err(format!("option '{}' doesn't take a value", name));
+        }
+        let mut value = || -> Result<String, String> {
+            match &inline_value {
+                Some(v) => Ok(v.clone()),
+                None => args
+                    .next()
+                    .ok_or_else(|| format!("option '{}' needs a value", name)),
+            }
+        };
+
+        match name.as_str() {
+            "-h" | "--help" => options.help = true,
+            "--bvh-stats" => options.bvh_stats = true,
+            "-o" | "--output" => options.output = Some(PathBuf::from(value()?)),
+            "-f" | "--format" => {
+                let v = value()?;
+                options.format =
+                    Some(parse_format(&v).ok_or_else(|| format!("unknown format '{}'", v))?);
+            }
+            "-w" | "--width" => options.width = Some(parse_number(&name, &value()?, 1)?),
+            "-a" | "--aspect" => options.aspect_ratio = Some(parse_aspect(&value()?)?),
+            "-s" | "--spp" => options.samples_per_pixel = Some(parse_number(&name, &value()?, 1)?),
+            "--adaptive" => {
+                let v = value()?;
+                let threshold: f64 = v
+                    .parse()
+                    .map_err(|_| format!("invalid value '{}' for {}", v, name))?;
+                if !(threshold.is_finite() && threshold >= 0.0) {
+                    return Err(format!("{} must be a non-negative number", name));
+                }
+                options.adaptive_threshold = Some(threshold);
+            }
+            "--adaptive-min" => {
+                options.adaptive_min_samples = Some(parse_number(&name, &value()?, 1)?)
+            }
+            "--sample-map" => options.sample_map = Some(PathBuf::from(value()?)),
+            "--sample-layer" => options.sample_layer = true,
+            "-d" | "--depth" => options.max_depth = Some(parse_number(&name, &value()?, 1)?),
+            "--roulette-depth" => options.roulette_depth = Some(parse_number(&name, &value()?, 0)?),
+            "--vfov" => {
+                let v = value()?;
+                let vfov: f64 = v
+                    .parse()
+                    .map_err(|_| format!("invalid value '{}' for {}", v, name))?;
+                // Written so that NaN fails too.
+                if !(vfov > 0.0 && vfov < 180.0) {
+                    return Err(format!("{} must be between 0 and 180 degrees", name));
+                }
+                options.vfov = Some(vfov);
+            }
+            "--seed" => options.seed = Some(parse_number(&name, &value()?, 0)?),
+            "-t" | "--threads" => options.threads = Some(parse_number(&name, &value()?, 0)?),
+            "-i" | "--integrator" => {
+                let v = value()?;
+                options.integrator = Some(
+                    Integrator::from_name(&v)
+                        .ok_or_else(|| format!("unknown integrator '{}'", v))?,
+                );
+            }
+            "--sampler" => {
+                let v = value()?;
+                options.sampler = Some(
+                    SamplerType::from_name(&v).ok_or_else(|| format!("unknown sampler '{}'", v))?,
+                );
+            }
+            "--filter" => {
+                let v = value()?;
+                options.filter =
+                    Some(Filter::from_name(&v).ok_or_else(|| format!("unknown filter '{}'", v))?);
+            }
+            "--filter-radius" => {
+                let v = value()?;
+                let radius: f64 = v
+                    .parse()
+                    .map_err(|_| format!("invalid value '{}' for {}", v, name))?;
+                if !(radius.is_finite() && radius > 0.0) {
+                    return Err(format!("{} must be a positive number", name));
+                }
+                options.filter_radius = Some(radius);
+            }
+            "--bvh" => {
+                let v = value()?;
+                options.split_method = Some(
+                    SplitMethod::from_name(&v)
+                        .ok_or_else(|| format!("unknown BVH method '{}'", v))?,
+                );
+            }
+            _ => return Err(format!("unknown option '{}'", name)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_format(name: &str) -> Option<ImageFormat> {
+    match name {
+        "ppm" => Some(ImageFormat::Ppm),
+        "png" => Some(ImageFormat::Png(BitDepth::Eight)),
+        "png16" => Some(ImageFormat::Png(BitDepth::Sixteen)),
+        "exr" => Some(ImageFormat::Exr(PixelType::Half)),
+        "exr-float" => Some(ImageFormat::Exr(PixelType::Float)),
+        "hdr" => Some(ImageFormat::Hdr),
+        _ => None,
+    }
+}
+
+fn parse_number<T: std::str::FromStr + PartialOrd + std::fmt::Display>(
+    name: &str,
+    value: &str,
+    min: T,
+) -> Result<T, String> {
+    let n: T = value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, name))?;
+    if n < min {
+        return Err(format!("{} must be at least {}", name, min));
+    }
+    Ok(n)
+}
+
+fn parse_aspect(value: &str) -> Result<f64, String> {
+    let invalid = || format!("invalid aspect ratio '{}'", value);
+    let ratio = match value.split_once(':') {
+        Some((w, h)) => {
+            let w: f64 = w.parse().map_err(|_| invalid())?;
+            let h: f64 = h.parse().map_err(|_| invalid())?;
+            w / h
+        }
+        None => value.parse().map_err(|_| invalid())?,
+    };
+    if !ratio.is_finite() || ratio <= 0.0 {
+        return Err(invalid());
+    }
+    Ok(ratio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn values_may_follow_or_be_joined_with_equals() {
+        let options = parse(&[
+            "scene.toml",
+            "--spp=32",
+            "-w",
+            "200",
+            "--aspect=16:9",
+            "--adaptive",
+            "0.05",
+            "--vfov=40",
+            "--filter-radius",
+            "1.5",
+            "--output=a=b.png",
+        ])
+        .unwrap();
+        assert_eq!(options.scene, Some(PathBuf::from("scene.toml")));
+        assert_eq!(options.samples_per_pixel, Some(32));
+        assert_eq!(options.width, Some(200));
+        assert_eq!(options.aspect_ratio, Some(16.0 / 9.0));
+        assert_eq!(options.adaptive_threshold, Some(0.05));
+        assert_eq!(options.vfov, Some(40.0));
+        assert_eq!(options.filter_radius, Some(1.5));
+        assert_eq!(options.output, Some(PathBuf::from("a=b.png")));
+    }
+
+    #[test]
+    fn missing_values_and_unexpected_values_are_errors() {
+        assert_eq!(
+            parse(&["--spp"]).err().unwrap(),
+            "option '--spp' needs a value"
+        );
+        assert_eq!(
+            parse(&["--bvh-stats=yes"]).err().unwrap(),
+            "option '--bvh-stats' doesn't take a value"
+        );
+        assert_eq!(
+            parse(&["a.toml", "b.toml"]).err().unwrap(),
+            "unexpected argument 'b.toml'"
+        );
+        assert_eq!(parse(&["--nope"]).err().unwrap(), "unknown option '--nope'");
+    }
+
+    #[test]
+    fn out_of_range_and_nan_values_are_rejected() {
+        let cases = [
+            (["--spp", "0"], "--spp must be at least 1"),
+            (["--width", "x"], "invalid value 'x' for --width"),
+            (["--aspect", "NaN"], "invalid aspect ratio 'NaN'"),
+            (["--aspect", "1:0"], "invalid aspect ratio '1:0'"),
+            (
+                ["--adaptive", "-0.1"],
+                "--adaptive must be a non-negative number",
+            ),
+            (
+                ["--adaptive", "NaN"],
+                "--adaptive must be a non-negative number",
+            ),
+            (
+                ["--adaptive", "inf"],
+                "--adaptive must be a non-negative number",
+            ),
+            (
+                ["--vfov", "180"],
+                "--vfov must be between 0 and 180 degrees",
+            ),
+            (
+                ["--vfov", "NaN"],
+                "--vfov must be between 0 and 180 degrees",
+            ),
+            (
+                ["--filter-radius", "0"],
+                "--filter-radius must be a positive number",
+            ),
+            (
+                ["--filter-radius", "NaN"],
+                "--filter-radius must be a positive number",
+            ),
+            (
+                ["--filter-radius", "inf"],
+                "--filter-radius must be a positive number",
+            ),
+        ];
+        for (args, message) in cases {
+            assert_eq!(parse(&args).err().unwrap(), message, "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+#![allow(dead_code)]
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::*;
+use crate::interval::{self, *};
+use crate::material::{Isotropic, Material};
+use crate::ray::*;
+use crate::rtweekend::random_double;
+use crate::texture::Texture;
+use crate::vec3::*;
+
+// Volume of uniform density filling a closed (convex) boundary, e.g. smoke or fog. Rays travel an
+// exponentially distributed distance through it before they scatter.
+#[derive(Clone)]
+pub struct ConstantMedium {
+    boundary: Hittable,
+    neg_inv_density: f64,
+    phase_function: Material,
+}
+
+impl ConstantMedium {
+    pub fn new_use(boundary: Hittable, density: f64, albedo: Color) -> Self {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Material::Isotropic(Arc::new(Isotropic::new(albedo))),
+        }
+    }
+
+    pub fn new_texture(boundary: Hittable, density: f64, tex: Texture) -> Self {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Material::Isotropic(Arc::new(Isotropic::new_texture(tex))),
+        }
+    }
+
+    pub fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        // Find where the ray enters and leaves the boundary, anywhere along its line.
+        let mut rec1 = HitRecord::new();
+        let mut rec2 = HitRecord::new();
+
+        if !self.boundary.hit(r, interval::UNIVERSAL, &mut rec1) {
+            return false;
+        }
+
+        if !self.boundary.hit(
+            r,
+            Interval::new_use(rec1.t + 0.0001, f64::INFINITY),
+            &mut rec2,
+        ) {
+            return false;
+        }
+
+        // Clip the span inside the volume to the part of the ray we're asked about.
+        if rec1.t < ray_t.min {
+            rec1.t = ray_t.min;
+        }
+        if rec2.t > ray_t.max {
+            rec2.t = ray_t.max;
+        }
+
+        if rec1.t >= rec2.t {
+            return false;
+        }
+
+        if rec1.t < 0.0 {
+            rec1.t = 0.0;
+        }
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
+        // The free-flight distance comes from the thread's random stream, not the sampler: a ray
+        // may test this medium several times (shadow rays, overlapping media), and every test
+        // needs an independent value, which a fixed sample dimension can't give. The stream is
+        // reseeded for every pixel sample, so renders stay reproducible.
+        let hit_distance = self.neg_inv_density * random_double().ln();
+
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        rec.t = rec1.t + hit_distance / ray_length;
+        rec.p = r.at(rec.t);
+
+        rec.normal = Vec3::new_use(1.0, 0.0, 0.0); // arbitrary
+        rec.geometric_normal = rec.normal;
+        rec.front_face = true; // also arbitrary
+        rec.u = 0.0;
+        rec.v = 0.0;
+        rec.mat = Some(self.phase_function.clone());
+
+        true
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
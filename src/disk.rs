@@ -0,0 +1,101 @@
+#![allow(dead_code)]
+use crate::aabb::Aabb;
+use crate::hittable::*;
+use crate::interval::*;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::*;
+use crate::vec3::*;
+use std::f64::consts::PI;
+
+// Flat disk facing along `normal`. UVs map the disk's bounding square onto [0,1]x[0,1].
+#[derive(Clone)]
+pub struct Disk {
+    center: Point3,
+    radius: f64,
+    normal: Vec3,
+    basis: Onb,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Disk {
+    pub fn new_use(center: Point3, normal: Vec3, radius: f64, mat_t: Material) -> Self {
+        let normal = unit_vector(normal);
+
+        // Along each axis the disk reaches radius * sin(angle between the axis and the normal).
+        let extent = |axis: usize| {
+            let r = radius * (1.0 - normal[axis] * normal[axis]).max(0.0).sqrt();
+            Interval::new_use(center[axis] - r, center[axis] + r)
+        };
+        let bbox = Aabb::new_use(extent(0), extent(1), extent(2)).padded();
+
+        Disk {
+            center,
+            radius,
+            normal,
+            basis: Onb::new(normal),
+            mat: mat_t,
+            bbox,
+        }
+    }
+
+    pub fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = dot(self.normal, *r.direction());
+
+        // No hit if the ray is parallel to the disk.
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = dot(self.normal, self.center - *r.origin()) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        let intersection = r.at(t);
+        let planar = intersection - self.center;
+        if planar.length_squared() > self.radius * self.radius {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = intersection;
+        rec.u = 0.5 * (1.0 + dot(planar, self.basis.u()) / self.radius);
+        rec.v = 0.5 * (1.0 + dot(planar, self.basis.v()) / self.radius);
+        rec.mat = Some(self.mat.clone());
+        rec.set_face_normal(r, self.normal);
+
+        true
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.mat
+    }
+
+    pub fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        let mut rec = HitRecord::new();
+        let r = Ray::new_with_time(&origin, &direction, time);
+        if !self.hit(r, Interval::new_use(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = dot(direction, rec.normal).abs() / direction.length();
+        let area = PI * self.radius * self.radius;
+
+        distance_squared / (cosine * area)
+    }
+
+    pub fn random(&self, origin: Point3, _time: f64, u: [f64; 2]) -> Vec3 {
+        // Uniform over the area: the square root keeps points from bunching at the center.
+        let r = self.radius * u[0].sqrt();
+        let phi = 2.0 * PI * u[1];
+        let p = self.center + r * phi.cos() * self.basis.u() + r * phi.sin() * self.basis.v();
+        p - origin
+    }
+}
@@ -0,0 +1,225 @@
+#![allow(dead_code)]
+// Minimal OpenEXR encoder: single-part, uncompressed scanline image with half or float channels.
+
+use std::io::{self, Write};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PixelType {
+    Half,
+    Float,
+}
+
+impl PixelType {
+    fn id(&self) -> i32 {
+        match self {
+            PixelType::Half => 1,
+            PixelType::Float => 2,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            PixelType::Half => 2,
+            PixelType::Float => 4,
+        }
+    }
+}
+
+// A named channel holding width * height linear values, row by row from the top.
+pub struct Channel {
+    pub name: String,
+    pub values: Vec<f32>,
+}
+
+pub fn write_exr<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    pixel_type: PixelType,
+    channels: &mut [Channel],
+) -> io::Result<()> {
+    // Channels are stored in alphabetical order, both in the header and in each scanline.
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+
+    // Version 2; set the long-names flag if any channel name exceeds 31 bytes.
+    let long_names = channels.iter().any(|c| c.name.len() > 31);
+    let version: u32 = if long_names { 2 | 0x400 } else { 2 };
+    header.extend_from_slice(&version.to_le_bytes());
+
+    let mut chlist = Vec::new();
+    for channel in channels.iter() {
+        chlist.extend_from_slice(channel.name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&pixel_type.id().to_le_bytes());
+        chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // xSampling
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // ySampling
+    }
+    chlist.push(0);
+    write_attribute(&mut header, "channels", "chlist", &chlist);
+
+    write_attribute(&mut header, "compression", "compression", &[0]);
+
+    let mut window = Vec::new();
+    for v in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // Line offset table: one uncompressed scanline per chunk.
+    let line_size = width * channels.len() * pixel_type.size();
+    let chunk_size = 8 + line_size;
+    let table_end = header.len() + 8 * height;
+    for y in 0..height {
+        let offset = (table_end + y * chunk_size) as u64;
+        header.extend_from_slice(&offset.to_le_bytes());
+    }
+    out.write_all(&header)?;
+
+    let mut line = Vec::with_capacity(chunk_size);
+    for y in 0..height {
+        line.clear();
+        line.extend_from_slice(&(y as i32).to_le_bytes());
+        line.extend_from_slice(&(line_size as i32).to_le_bytes());
+        for channel in channels.iter() {
+            for &value in &channel.values[y * width..(y + 1) * width] {
+                match pixel_type {
+                    PixelType::Half => line.extend_from_slice(&f32_to_half(value).to_le_bytes()),
+                    PixelType::Float => line.extend_from_slice(&value.to_le_bytes()),
+                }
+            }
+        }
+        out.write_all(&line)?;
+    }
+
+    out.flush()
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+pub fn f32_to_half(value: f32) -> u16 {
+    // IEEE 754 binary32 to binary16, rounding to nearest even.
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x007F_FFFF;
+
+    if exponent == 0xFF {
+        // Infinity or NaN; keep NaNs quiet and non-zero.
+        let nan_bit = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7C00 | nan_bit;
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1F {
+        // Overflow to infinity.
+        return sign | 0x7C00;
+    }
+
+    if half_exponent <= 0 {
+        // Subnormal half, or underflow to zero.
+        if half_exponent < -10 {
+            return sign;
+        }
+        let full_mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        let mut half_mantissa = full_mantissa >> shift;
+        let remainder = full_mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        if remainder > halfway || (remainder == halfway && (half_mantissa & 1) != 0) {
+            half_mantissa += 1;
+        }
+        return sign | half_mantissa as u16;
+    }
+
+    let mut half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1FFF;
+    if remainder > 0x1000 || (remainder == 0x1000 && (half & 1) != 0) {
+        // Rounding may carry into the exponent, which correctly produces infinity at the top.
+        half += 1;
+    }
+    sign | half as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_of_normal_values() {
+        assert_eq!(f32_to_half(0.0), 0x0000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        assert_eq!(f32_to_half(1.0), 0x3C00);
+        assert_eq!(f32_to_half(-2.0), 0xC000);
+        assert_eq!(f32_to_half(0.333_333_34), 0x3555);
+        assert_eq!(f32_to_half(65504.0), 0x7BFF);
+        assert_eq!(f32_to_half(2f32.powi(-14)), 0x0400);
+    }
+
+    #[test]
+    fn half_rounds_to_nearest_even() {
+        // Exactly halfway between two halves goes to the even one, anything above rounds up.
+        assert_eq!(f32_to_half(1.0 + 2f32.powi(-11)), 0x3C00);
+        assert_eq!(f32_to_half(1.0 + 3.0 * 2f32.powi(-11)), 0x3C02);
+        assert_eq!(f32_to_half(1.0 + 2f32.powi(-11) + 2f32.powi(-20)), 0x3C01);
+        // Rounding up the largest mantissa carries into the exponent.
+        assert_eq!(f32_to_half(2.0 - 2f32.powi(-12)), 0x4000);
+    }
+
+    #[test]
+    fn half_overflows_to_infinity() {
+        assert_eq!(f32_to_half(65519.0), 0x7BFF);
+        assert_eq!(f32_to_half(65520.0), 0x7C00);
+        assert_eq!(f32_to_half(1e10), 0x7C00);
+        assert_eq!(f32_to_half(-1e10), 0xFC00);
+        assert_eq!(f32_to_half(f32::INFINITY), 0x7C00);
+        assert_eq!(f32_to_half(f32::NEG_INFINITY), 0xFC00);
+        let nan = f32_to_half(f32::NAN);
+        assert_eq!(nan & 0x7C00, 0x7C00);
+        assert_ne!(nan & 0x03FF, 0);
+    }
+
+    #[test]
+    fn half_denormals() {
+        assert_eq!(f32_to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_half(-2f32.powi(-24)), 0x8001);
+        assert_eq!(f32_to_half(2f32.powi(-15)), 0x0200);
+        assert_eq!(f32_to_half(2f32.powi(-14) - 2f32.powi(-24)), 0x03FF);
+        // Halfway cases round to even; below half the smallest denormal is zero.
+        assert_eq!(f32_to_half(2f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_half(3.0 * 2f32.powi(-25)), 0x0002);
+        assert_eq!(f32_to_half(1.5 * 2f32.powi(-25)), 0x0001);
+        assert_eq!(f32_to_half(2f32.powi(-40)), 0x0000);
+        // The largest denormal rounds up into the smallest normal.
+        assert_eq!(f32_to_half(2f32.powi(-14) - 2f32.powi(-26)), 0x0400);
+    }
+}
@@ -0,0 +1,155 @@
+#![allow(dead_code)]
+use std::f64::consts::PI;
+
+// Pixel reconstruction filters. Every sample is splatted into each pixel whose center lies within
+// the filter radius, weighted by the filter at the offset from that center; a pixel is the
+// weighted average of the samples it received.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Filter {
+    // Equal weights, the plain per-pixel average at the default radius.
+    Box,
+    // Weights falling off linearly to zero at the radius.
+    Tent,
+    // Gaussian with a standard deviation of a third of the radius, shifted to reach zero there.
+    Gaussian,
+    // Mitchell-Netravali cubic with B = C = 1/3; slightly sharpening.
+    Mitchell,
+    // Windowed sinc, sharpest of all but prone to ringing around bright edges.
+    Lanczos,
+}
+
+impl Filter {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "box" => Some(Filter::Box),
+            "tent" => Some(Filter::Tent),
+            "gaussian" => Some(Filter::Gaussian),
+            "mitchell" => Some(Filter::Mitchell),
+            "lanczos" => Some(Filter::Lanczos),
+            _ => None,
+        }
+    }
+
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+            Filter::Lanczos => 3.0,
+        }
+    }
+
+    pub fn evaluate(&self, dx: f64, dy: f64, radius: f64) -> f64 {
+        // Weight of a sample at offset (dx, dy) pixels from a pixel center. Zero outside the
+        // radius; Mitchell and Lanczos go negative between their lobes.
+        if dx.abs() > radius || dy.abs() > radius {
+            return 0.0;
+        }
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => (radius - dx.abs()) * (radius - dy.abs()),
+            Filter::Gaussian => {
+                let sigma = radius / 3.0;
+                let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                let edge = gaussian(radius);
+                f64::max(0.0, gaussian(dx) - edge) * f64::max(0.0, gaussian(dy) - edge)
+            }
+            Filter::Mitchell => mitchell(2.0 * dx / radius) * mitchell(2.0 * dy / radius),
+            Filter::Lanczos => lanczos(dx, radius) * lanczos(dy, radius),
+        }
+    }
+}
+
+fn mitchell(x: f64) -> f64 {
+    // The cubic over [-2, 2].
+    let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+    let x = x.abs();
+    if x < 1.0 {
+        ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b))
+            / 6.0
+    } else if x < 2.0 {
+        ((-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        0.0
+    }
+}
+
+fn lanczos(x: f64, radius: f64) -> f64 {
+    sinc(x) * sinc(x / radius)
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [Filter; 5] = [
+        Filter::Box,
+        Filter::Tent,
+        Filter::Gaussian,
+        Filter::Mitchell,
+        Filter::Lanczos,
+    ];
+
+    #[test]
+    fn weights_are_zero_outside_the_radius() {
+        for filter in FILTERS {
+            for radius in [0.5, filter.default_radius(), 2.5] {
+                for offset in [radius + 1e-9, radius + 0.5, 10.0] {
+                    for inside in [0.0, 0.5 * radius] {
+                        for (dx, dy) in [(offset, inside), (inside, -offset), (-offset, offset)] {
+                            assert_eq!(
+                                filter.evaluate(dx, dy, radius),
+                                0.0,
+                                "{:?} radius {} at ({}, {})",
+                                filter,
+                                radius,
+                                dx,
+                                dy
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn weights_peak_at_the_center() {
+        for filter in FILTERS {
+            for radius in [0.5, filter.default_radius(), 2.5] {
+                let center = filter.evaluate(0.0, 0.0, radius);
+                assert!(center > 0.0, "{:?} radius {}", filter, radius);
+                let steps = 20;
+                for i in -steps..=steps {
+                    for j in -steps..=steps {
+                        let dx = radius * i as f64 / steps as f64;
+                        let dy = radius * j as f64 / steps as f64;
+                        assert!(
+                            filter.evaluate(dx, dy, radius) <= center,
+                            "{:?} radius {} at ({}, {})",
+                            filter,
+                            radius,
+                            dx,
+                            dy
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+#![allow(dead_code)]
+// Radiance RGBE (.hdr) encoder using flat, uncompressed scanlines.
+
+use std::io::{self, Write};
+
+use crate::vec3::Color;
+
+pub fn write_hdr<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    pixels: &[Color],
+) -> io::Result<()> {
+    write!(out, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n")?;
+    writeln!(out, "-Y {} +X {}", height, width)?;
+
+    let mut line = Vec::with_capacity(width * 4);
+    for row in pixels.chunks(width.max(1)) {
+        line.clear();
+        for pixel_color in row {
+            line.extend_from_slice(&color_to_rgbe(*pixel_color));
+        }
+        out.write_all(&line)?;
+    }
+
+    out.flush()
+}
+
+pub fn color_to_rgbe(pixel_color: Color) -> [u8; 4] {
+    // Shared exponent encoding. Negative and non-finite components can't be represented.
+    let clean = |c: f64| if c.is_finite() && c > 0.0 { c } else { 0.0 };
+    let r = clean(pixel_color.x());
+    let g = clean(pixel_color.y());
+    let b = clean(pixel_color.z());
+
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    let mut m = v / 2f64.powi(e);
+    if m >= 1.0 {
+        m *= 0.5;
+        e += 1;
+    }
+    if e > 127 {
+        return [255, 255, 255, 255];
+    }
+
+    let scale = m * 256.0 / v;
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (e + 128) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(rgbe: [u8; 4]) -> [f64; 3] {
+        // Radiance's decoding, with the mantissas taken at the middle of their bins.
+        if rgbe[3] == 0 {
+            return [0.0; 3];
+        }
+        let scale = 2f64.powi(rgbe[3] as i32 - 136);
+        [0, 1, 2].map(|c| (rgbe[c] as f64 + 0.5) * scale)
+    }
+
+    #[test]
+    fn rgbe_of_exact_values() {
+        assert_eq!(
+            color_to_rgbe(Color::new_use(1.0, 1.0, 1.0)),
+            [128, 128, 128, 129]
+        );
+        assert_eq!(
+            color_to_rgbe(Color::new_use(0.5, 0.25, 0.125)),
+            [128, 64, 32, 128]
+        );
+        assert_eq!(color_to_rgbe(Color::new_use(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rgbe_drops_negative_and_non_finite_components() {
+        assert_eq!(
+            color_to_rgbe(Color::new_use(-1.0, 1.0, f64::NAN)),
+            [0, 128, 0, 129]
+        );
+        assert_eq!(
+            color_to_rgbe(Color::new_use(-1.0, -2.0, -0.5)),
+            [0, 0, 0, 0]
+        );
+        assert_eq!(
+            color_to_rgbe(Color::new_use(f64::INFINITY, 2.0, 0.0)),
+            [0, 128, 0, 130]
+        );
+        assert_eq!(
+            color_to_rgbe(Color::new_use(1e300, 0.0, 0.0)),
+            [255, 255, 255, 255]
+        );
+    }
+
+    #[test]
+    fn rgbe_round_trips_the_brightest_channel() {
+        for v in [1e-20, 0.001, 0.3, 0.999_999, 1.0, 3.7, 1000.0, 6.5e12] {
+            let decoded = decode(color_to_rgbe(Color::new_use(v * 0.5, v, v * 0.01)));
+            assert!(
+                (decoded[1] - v).abs() <= v / 256.0,
+                "{} decoded as {}",
+                v,
+                decoded[1]
+            );
+            assert!((decoded[0] - v * 0.5).abs() <= v / 256.0);
+        }
+    }
+
+    #[test]
+    fn write_hdr_writes_header_and_scanlines() {
+        let pixels = [Color::new_use(1.0, 1.0, 1.0), Color::new_use(0.0, 0.0, 0.0)];
+        let mut out = Vec::new();
+        write_hdr(&mut out, 2, 1, &pixels).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n";
+        assert_eq!(&out[..header.len()], header);
+        assert_eq!(&out[header.len()..], &[128, 128, 128, 129, 0, 0, 0, 0]);
+    }
+}
@@ -1,14 +1,32 @@
 #![allow(dead_code)]
+use crate::aabb::Aabb;
+use crate::bvh::BvhNode;
+use crate::constant_medium::ConstantMedium;
+use crate::disk::Disk;
+use crate::instance::Instance;
 use crate::interval::*;
 use crate::material::*;
+use crate::plane::Plane;
+use crate::quad::Quad;
 use crate::ray::*;
 use crate::sphere::Sphere;
+use crate::triangle::Triangle;
 use crate::vec3::*;
 
+use std::sync::Arc;
+
 #[derive(Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum Hittable {
     Sphere(Box<Sphere>),
+    Triangle(Box<Triangle>),
+    Quad(Box<Quad>),
+    Plane(Box<Plane>),
+    Disk(Box<Disk>),
+    Instance(Box<Instance>),
+    ConstantMedium(Box<ConstantMedium>),
     HittableList(Box<HittableList>),
+    Bvh(Box<BvhNode>),
 }
 
 impl Hittable {
@@ -16,6 +34,94 @@ impl Hittable {
         match self {
             Hittable::HittableList(h) => h.hit(r, ray_t, rec),
             Hittable::Sphere(s) => s.hit(r, ray_t, rec),
+            Hittable::Triangle(t) => t.hit(r, ray_t, rec),
+            Hittable::Quad(q) => q.hit(r, ray_t, rec),
+            Hittable::Plane(p) => p.hit(r, ray_t, rec),
+            Hittable::Disk(d) => d.hit(r, ray_t, rec),
+            Hittable::Instance(i) => i.hit(r, ray_t, rec),
+            Hittable::ConstantMedium(c) => c.hit(r, ray_t, rec),
+            Hittable::Bvh(b) => b.hit(r, ray_t, rec),
+        }
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        match self {
+            Hittable::HittableList(h) => h.bounding_box(),
+            Hittable::Sphere(s) => s.bounding_box(),
+            Hittable::Triangle(t) => t.bounding_box(),
+            Hittable::Quad(q) => q.bounding_box(),
+            Hittable::Plane(p) => p.bounding_box(),
+            Hittable::Disk(d) => d.bounding_box(),
+            Hittable::Instance(i) => i.bounding_box(),
+            Hittable::ConstantMedium(c) => c.bounding_box(),
+            Hittable::Bvh(b) => b.bounding_box(),
+        }
+    }
+
+    pub fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        // Solid-angle density with which `random` picks `direction`; zero for shapes that can't be
+        // sampled.
+        match self {
+            Hittable::Sphere(s) => s.pdf_value(origin, direction, time),
+            Hittable::Triangle(t) => t.pdf_value(origin, direction, time),
+            Hittable::Quad(q) => q.pdf_value(origin, direction, time),
+            Hittable::Disk(d) => d.pdf_value(origin, direction, time),
+            Hittable::HittableList(h) => h.pdf_value(origin, direction, time),
+            Hittable::Instance(i) => i.pdf_value(origin, direction, time),
+            _ => 0.0,
+        }
+    }
+
+    pub fn random(&self, origin: Point3, time: f64, u: [f64; 2]) -> Vec3 {
+        // Direction from `origin` towards a point on the shape, placed by the sample `u` in
+        // [0,1)^2.
+        match self {
+            Hittable::Sphere(s) => s.random(origin, time, u),
+            Hittable::Triangle(t) => t.random(origin, time, u),
+            Hittable::Quad(q) => q.random(origin, time, u),
+            Hittable::Disk(d) => d.random(origin, time, u),
+            Hittable::HittableList(h) => h.random(origin, time, u),
+            Hittable::Instance(i) => i.random(origin, time, u),
+            _ => Vec3::new_use(1.0, 0.0, 0.0),
+        }
+    }
+
+    fn collect_lights(&self, lights: &mut HittableList) {
+        // Media and infinite planes can't be sampled, so they're never lights.
+        let mat = match self {
+            Hittable::Sphere(s) => s.material(),
+            Hittable::Triangle(t) => t.material(),
+            Hittable::Quad(q) => q.material(),
+            Hittable::Disk(d) => d.material(),
+            Hittable::HittableList(h) => {
+                for object in &h.objects {
+                    object.collect_lights(lights);
+                }
+                return;
+            }
+            Hittable::Bvh(b) => {
+                for child in b.children() {
+                    child.collect_lights(lights);
+                }
+                return;
+            }
+            Hittable::Instance(i) => {
+                let mut inner = HittableList::new();
+                i.object().collect_lights(&mut inner);
+                let object = match inner.objects.len() {
+                    0 => return,
+                    1 => inner.objects.pop().unwrap(),
+                    _ => Hittable::HittableList(Box::new(inner)),
+                };
+                lights.add(Hittable::Instance(Box::new(
+                    i.with_object(Arc::new(object)),
+                )));
+                return;
+            }
+            Hittable::Plane(_) | Hittable::ConstantMedium(_) => return,
+        };
+        if mat.is_emissive() {
+            lights.add(self.clone());
         }
     }
 }
@@ -24,12 +130,22 @@ impl Hittable {
 pub struct HitRecord {
     pub p: Point3,
     pub normal: Vec3,
+    pub geometric_normal: Vec3,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
+    pub barycentric: Vec3,
     pub front_face: bool,
-    pub mat: Material,
+    pub mat: Option<Material>, // Set by every successful hit
 }
 
 impl HitRecord {
+    pub fn material(&self) -> &Material {
+        self.mat
+            .as_ref()
+            .expect("hit records get their material from the object that was hit")
+    }
+
     pub fn set_face_normal(&mut self, r: Ray, outward_normal: Vec3) {
         // Sets the hit record normal vector
         // NOTE: the parameter `outward_normal` is assumed to have unit length.
@@ -41,6 +157,19 @@ impl HitRecord {
         } else {
             self.normal = -outward_normal;
         }
+        self.geometric_normal = self.normal;
+    }
+
+    pub fn set_shading_normal(&mut self, shading_normal: Vec3) {
+        // Replaces the normal used for shading (e.g. an interpolated vertex normal), flipped onto
+        // the same side as the geometric normal. Call after `set_face_normal`.
+        // NOTE: the parameter `shading_normal` is assumed to have unit length.
+
+        if dot(shading_normal, self.geometric_normal) >= 0.0 {
+            self.normal = shading_normal;
+        } else {
+            self.normal = -shading_normal;
+        }
     }
 }
 
@@ -49,9 +178,13 @@ impl HitRecord {
         HitRecord {
             p: Point3::new(),
             normal: Vec3::new(),
+            geometric_normal: Vec3::new(),
             t: 0.0,
+            u: 0.0,
+            v: 0.0,
+            barycentric: Vec3::new(),
             front_face: false,
-            mat: Material::new(),
+            mat: None,
         }
     }
 }
@@ -60,13 +193,23 @@ impl HitRecord {
 #[derive(Clone)]
 pub struct HittableList {
     objects: Vec<Hittable>,
+    bbox: Aabb,
 }
 
 impl HittableList {
     pub fn new() -> Self {
         HittableList {
             objects: Vec::new(),
+            bbox: Aabb::new(),
+        }
+    }
+
+    pub fn new_from(objects: Vec<Hittable>) -> Self {
+        let mut list = HittableList::new();
+        for object in objects {
+            list.add(object);
         }
+        list
     }
 
     pub fn new_use(&mut self, object: Hittable) {
@@ -75,26 +218,76 @@ impl HittableList {
 
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.bbox = Aabb::new();
     }
 
     pub fn add(&mut self, object: Hittable) {
+        self.bbox = Aabb::new_enclosing(&self.bbox, &object.bounding_box());
         self.objects.push(object);
     }
 
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn into_objects(self) -> Vec<Hittable> {
+        self.objects
+    }
+
+    pub fn lights(&self) -> HittableList {
+        // The emissive primitives that can be sampled directly, searching nested lists, BVHs and
+        // instances. Lights inside an instance come back wrapped in its transform.
+        let mut lights = HittableList::new();
+        for object in &self.objects {
+            object.collect_lights(&mut lights);
+        }
+        lights
+    }
+
+    pub fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        // `random` picks every object with equal probability.
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects
+            .iter()
+            .map(|object| weight * object.pdf_value(origin, direction, time))
+            .sum()
+    }
+
+    pub fn random(&self, origin: Point3, time: f64, u: [f64; 2]) -> Vec3 {
+        // The first coordinate picks the object and is then stretched back over [0,1), so the
+        // object still gets a well distributed sample.
+        let n = self.objects.len();
+        let scaled = u[0] * n as f64;
+        let index = (scaled as usize).min(n - 1);
+        let u0 = (scaled - index as f64).min(1.0 - f64::EPSILON / 2.0);
+        self.objects[index].random(origin, time, [u0, u[1]])
+    }
+
+    pub fn into_bvh(self) -> Hittable {
+        // Wrap the list's objects in a bounding volume hierarchy.
+        Hittable::Bvh(Box::new(BvhNode::new(self)))
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
     pub fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
-        let mut temp_rec = HitRecord::new();
+        // Objects only write to `rec` when they report a hit, so each closer hit overwrites it.
         let mut hit_anything = false;
         let mut closest_so_far = ray_t.max;
 
         for object in &self.objects {
-            if object.hit(
-                r,
-                Interval::new_use(ray_t.min, closest_so_far),
-                &mut temp_rec,
-            ) {
+            if object.hit(r, Interval::new_use(ray_t.min, closest_so_far), rec) {
                 hit_anything = true;
-                closest_so_far = temp_rec.clone().t;
-                *rec = temp_rec.clone();
+                closest_so_far = rec.t;
             }
         }
 
@@ -0,0 +1,60 @@
+#![allow(dead_code)]
+use crate::vec3::Color;
+
+// Framebuffer of linear (not gamma-corrected, unclamped) pixel colors, stored row by row from the
+// top-left corner.
+#[derive(Clone)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![Color::new(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, i: usize, j: usize) -> Color {
+        self.pixels[j * self.width + i]
+    }
+
+    pub fn set_pixel(&mut self, i: usize, j: usize, pixel_color: Color) {
+        self.pixels[j * self.width + i] = pixel_color;
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_are_stored_row_by_row() {
+        let mut image = Image::new(3, 2);
+        image.set_pixel(2, 0, Color::new_use(1.0, 0.0, 0.0));
+        image.set_pixel(0, 1, Color::new_use(0.0, 1.0, 0.0));
+
+        assert_eq!(image.width(), 3);
+        assert_eq!(image.height(), 2);
+        assert_eq!(image.pixel(2, 0).x(), 1.0);
+        assert_eq!(image.pixels()[2].x(), 1.0);
+        assert_eq!(image.pixels()[3].y(), 1.0);
+        assert_eq!(image.pixels()[5].x(), 0.0);
+    }
+}
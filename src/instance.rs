@@ -0,0 +1,224 @@
+#![allow(dead_code)]
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::*;
+use crate::interval::*;
+use crate::ray::*;
+use crate::transform::{AnimatedTransform, Transform};
+use crate::vec3::*;
+
+// An object placed in the world by an affine transform. The object itself is shared, so the same
+// geometry can be instanced any number of times.
+#[derive(Clone)]
+pub struct Instance {
+    object: Arc<Hittable>,
+    transform: Transform,
+    animation: Option<Box<AnimatedTransform>>,
+    bbox: Aabb,
+}
+
+impl Instance {
+    pub fn new_use(object: Arc<Hittable>, transform: Transform) -> Self {
+        let bbox = transform.bounding_box(&object.bounding_box());
+        Instance {
+            object,
+            transform,
+            animation: None,
+            bbox,
+        }
+    }
+
+    pub fn new_animated(object: Arc<Hittable>, animation: AnimatedTransform) -> Self {
+        // The transform is evaluated at each ray's time; the box covers the whole animation.
+        let bbox = animation.bounding_box(&object.bounding_box());
+        Instance {
+            object,
+            transform: animation.at(f64::NEG_INFINITY),
+            animation: Some(Box::new(animation)),
+            bbox,
+        }
+    }
+
+    pub fn with_object(&self, object: Arc<Hittable>) -> Self {
+        // The same placement (and animation) applied to a different object.
+        let bbox = match &self.animation {
+            Some(animation) => animation.bounding_box(&object.bounding_box()),
+            None => self.transform.bounding_box(&object.bounding_box()),
+        };
+        Instance {
+            object,
+            transform: self.transform,
+            animation: self.animation.clone(),
+            bbox,
+        }
+    }
+
+    pub fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let transform = self.transform_at(r.time());
+
+        // Transform the ray from world space to object space. The direction is left unnormalized,
+        // so hit distances are the same in both spaces.
+        let origin = transform.inverse_point(*r.origin());
+        let direction = transform.inverse_vector(*r.direction());
+        let object_r = Ray::new_with_time(&origin, &direction, r.time());
+
+        // Determine whether an intersection exists in object space (and if so, where).
+        if !self.object.hit(object_r, ray_t, rec) {
+            return false;
+        }
+
+        // Transform the intersection from object space back to world space. The inverse transpose
+        // keeps normals on the same side of the surface, so `front_face` still holds.
+        rec.p = transform.point(rec.p);
+        rec.normal = unit_vector(transform.normal(rec.normal));
+        rec.geometric_normal = unit_vector(transform.normal(rec.geometric_normal));
+
+        true
+    }
+
+    pub fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        // The object's density for the same ray in object space, converted to world-space solid
+        // angle. Normalizing the directions after the linear map A from world to object space
+        // stretches solid angles by |det A| / |A d|^3 for a unit direction d.
+        let transform = self.transform_at(time);
+        let object_direction = transform.inverse_vector(unit_vector(direction));
+        let length = object_direction.length();
+        let pdf = self.object.pdf_value(
+            transform.inverse_point(origin),
+            object_direction / length,
+            time,
+        );
+        if pdf == 0.0 {
+            return 0.0;
+        }
+
+        // det A is 1 / det M for the object-to-world map M, whose columns are the mapped axes.
+        let det = dot(
+            transform.vector(Vec3::new_use(1.0, 0.0, 0.0)),
+            cross(
+                transform.vector(Vec3::new_use(0.0, 1.0, 0.0)),
+                transform.vector(Vec3::new_use(0.0, 0.0, 1.0)),
+            ),
+        );
+        pdf / (det.abs() * length * length * length)
+    }
+
+    pub fn random(&self, origin: Point3, time: f64, u: [f64; 2]) -> Vec3 {
+        // Affine maps keep points on the object, so the object-space offset maps straight back.
+        let transform = self.transform_at(time);
+        transform.vector(self.object.random(transform.inverse_point(origin), time, u))
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    pub fn object(&self) -> &Hittable {
+        &self.object
+    }
+
+    fn transform_at(&self, time: f64) -> Transform {
+        match &self.animation {
+            Some(animation) => animation.at(time),
+            None => self.transform,
+        }
+    }
+
+    pub fn transform(&self) -> &Transform {
+        // The transform at the start of the animation, for animated instances.
+        &self.transform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::BvhBuilder;
+    use crate::material::{DiffuseLight, Material};
+    use crate::quad::Quad;
+
+    fn light() -> Material {
+        Material::DiffuseLight(Arc::new(DiffuseLight::new(Color::new_use(1.0, 1.0, 1.0))))
+    }
+
+    fn placement() -> Transform {
+        // Non-uniform scale and rotation, which change solid angles unevenly.
+        Transform::scale(Vec3::new_use(2.0, 0.5, 1.5))
+            .unwrap()
+            .then(&Transform::rotate(Vec3::new_use(1.0, 1.0, 0.0), 30.0))
+            .then(&Transform::translate(Vec3::new_use(0.5, 3.0, -1.0)))
+    }
+
+    #[test]
+    fn instanced_lights_match_world_space_lights() {
+        let (q, u, v) = (
+            Point3::new_use(-0.5, 0.0, -0.5),
+            Vec3::new_use(1.0, 0.0, 0.0),
+            Vec3::new_use(0.0, 0.0, 1.0),
+        );
+        let object = Hittable::Quad(Box::new(Quad::new_use(q, u, v, light())));
+        let transform = placement();
+        let instance = Instance::new_use(Arc::new(object), transform);
+        let world_quad = Quad::new_use(
+            transform.point(q),
+            transform.vector(u),
+            transform.vector(v),
+            light(),
+        );
+
+        let origins = [
+            Point3::new_use(0.0, 0.0, 0.0),
+            Point3::new_use(3.0, -1.0, 2.0),
+        ];
+        for origin in origins {
+            for k in 0..16 {
+                let sample = [(k % 4) as f64 / 4.0 + 0.1, (k / 4) as f64 / 4.0 + 0.05];
+                let direction = instance.random(origin, 0.0, sample);
+                let expected = world_quad.random(origin, 0.0, sample);
+                assert!((direction - expected).length() < 1e-9);
+
+                let pdf = instance.pdf_value(origin, direction, 0.0);
+                let expected = world_quad.pdf_value(origin, direction, 0.0);
+                assert!(
+                    (pdf - expected).abs() < 1e-9 * expected,
+                    "{} vs {}",
+                    pdf,
+                    expected
+                );
+            }
+        }
+        let away = Vec3::new_use(0.0, -1.0, 0.0);
+        assert_eq!(instance.pdf_value(Point3::new(), away, 0.0), 0.0);
+    }
+
+    #[test]
+    fn lights_are_found_inside_instances_and_bvhs() {
+        let mut mesh = HittableList::new();
+        mesh.add(Hittable::Quad(Box::new(Quad::new_use(
+            Point3::new(),
+            Vec3::new_use(1.0, 0.0, 0.0),
+            Vec3::new_use(0.0, 1.0, 0.0),
+            light(),
+        ))));
+        mesh.add(Hittable::Quad(Box::new(Quad::new_use(
+            Point3::new_use(0.0, 0.0, 2.0),
+            Vec3::new_use(1.0, 0.0, 0.0),
+            Vec3::new_use(0.0, 1.0, 0.0),
+            Material::new(),
+        ))));
+        let instance = Instance::new_use(Arc::new(BvhBuilder::new().build(mesh)), placement());
+
+        let mut world = HittableList::new();
+        world.add(Hittable::Instance(Box::new(instance)));
+        let lights = world.lights();
+        assert_eq!(lights.len(), 1);
+        match &lights.into_objects()[0] {
+            Hittable::Instance(i) => {
+                assert!(matches!(i.object(), Hittable::Quad(_)));
+                assert_eq!(i.transform(), &placement());
+            }
+            _ => panic!("the light should keep its instance transform"),
+        }
+    }
+}
@@ -1,18 +1,17 @@
 #![allow(dead_code)]
 use core::f64;
-use std::f64::{INFINITY, NEG_INFINITY};
 
 pub const EMPTY: Interval = Interval {
-    min: INFINITY,
-    max: NEG_INFINITY,
+    min: f64::INFINITY,
+    max: f64::NEG_INFINITY,
 };
 
 pub const UNIVERSAL: Interval = Interval {
-    min: NEG_INFINITY,
-    max: INFINITY,
+    min: f64::NEG_INFINITY,
+    max: f64::INFINITY,
 };
 
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 pub struct Interval {
     pub min: f64,
     pub max: f64,
@@ -21,8 +20,8 @@ pub struct Interval {
 impl Interval {
     pub fn new() -> Self {
         Interval {
-            min: INFINITY,
-            max: NEG_INFINITY,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
         }
     }
 
@@ -33,6 +32,14 @@ impl Interval {
         }
     }
 
+    pub fn new_enclosing(a: &Interval, b: &Interval) -> Self {
+        // Create the interval tightly enclosing the two input intervals.
+        Interval {
+            min: if a.min <= b.min { a.min } else { b.min },
+            max: if a.max >= b.max { a.max } else { b.max },
+        }
+    }
+
     pub fn size(&self) -> f64 {
         self.max - self.min
     }
@@ -54,6 +61,11 @@ impl Interval {
         }
         x
     }
+
+    pub fn expand(&self, delta: f64) -> Interval {
+        let padding = delta / 2.0;
+        Interval::new_use(self.min - padding, self.max + padding)
+    }
 }
 
 impl Default for Interval {
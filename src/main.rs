@@ -1,28 +1,178 @@
+mod aabb;
+mod bvh;
 mod camera;
+mod cli;
+mod constant_medium;
+mod disk;
+mod exr;
+mod filter;
+mod hdr;
 mod hittable;
+mod image;
+mod instance;
 mod interval;
 mod material;
+mod mesh;
+mod obj;
+mod onb;
+mod output;
+mod perlin;
+mod plane;
+mod png;
+mod quad;
 mod ray;
 mod rtweekend;
+mod sampler;
+mod scene;
 mod sphere;
+mod texture;
+mod transform;
+mod triangle;
 mod vec3;
 
+use bvh::{BvhBuilder, SplitMethod};
 use camera::*;
+use cli::Options;
 use hittable::{Hittable, HittableList};
 use material::{Dielectric, Lambertian, Material, Metal};
+use output::{write_exr_layers, write_image, write_ppm, write_to_stdout, ImageFormat};
 use ray::*;
-use rtweekend::{random_double, random_double_2};
+use rtweekend::{random_double, random_double_2, seed_random};
+use scene::{load_scene, Scene};
 use sphere::Sphere;
 use vec3::{Color, Vec3};
 
+use std::env;
+use std::error::Error;
+use std::io::{self, BufWriter};
+use std::process;
+use std::sync::Arc;
+
 // Left off 6.2
 
 fn main() {
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    if let Err(e) = run(&options) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    // Check the output format before spending time on the render.
+    let format = options.output_format()?;
+    let sample_map_format = match &options.sample_map {
+        Some(path) => ImageFormat::from_path(path)
+            .ok_or_else(|| format!("can't tell the image format of '{}'", path.display()))?,
+        None => ImageFormat::Ppm,
+    };
+    let sample_layer_type = match (options.sample_layer, format) {
+        (false, _) => None,
+        (true, Some(ImageFormat::Exr(pixel_type))) => Some(pixel_type),
+        (true, _) => return Err("--sample-layer needs EXR output".into()),
+    };
+
+    let Scene {
+        camera: mut cam,
+        world,
+    } = match &options.scene {
+        Some(path) => load_scene(path)?,
+        None => random_spheres_scene(options.seed.unwrap_or(0)),
+    };
+
+    if let Some(width) = options.width {
+        cam.image_width = width;
+    }
+    if let Some(aspect_ratio) = options.aspect_ratio {
+        cam.aspect_ratio = aspect_ratio;
+    }
+    if let Some(samples_per_pixel) = options.samples_per_pixel {
+        cam.samples_per_pixel = samples_per_pixel;
+    }
+    if let Some(threshold) = options.adaptive_threshold {
+        cam.adaptive_threshold = threshold;
+    }
+    if let Some(min_samples) = options.adaptive_min_samples {
+        cam.adaptive_min_samples = min_samples;
+    }
+    if let Some(max_depth) = options.max_depth {
+        cam.max_depth = max_depth;
+    }
+    if let Some(roulette_depth) = options.roulette_depth {
+        cam.roulette_depth = roulette_depth;
+    }
+    if let Some(vfov) = options.vfov {
+        cam.vfov = vfov;
+    }
+    if let Some(seed) = options.seed {
+        cam.seed = seed;
+    }
+    if let Some(threads) = options.threads {
+        cam.thread_count = threads;
+    }
+    if let Some(integrator) = options.integrator {
+        cam.integrator = integrator;
+    }
+    if let Some(sampler) = options.sampler {
+        cam.sampler = sampler;
+    }
+    if let Some(filter) = options.filter {
+        cam.filter = filter;
+    }
+    if let Some(filter_radius) = options.filter_radius {
+        cam.filter_radius = filter_radius;
+    }
+
+    let mut builder = BvhBuilder::new();
+    if options.bvh_stats {
+        for method in [SplitMethod::Median, SplitMethod::Midpoint, SplitMethod::Sah] {
+            builder.split_method = method;
+            let bvh = builder.build(world.clone());
+            eprintln!("BVH {:?}: {}", method, builder.stats(&bvh));
+        }
+    }
+    builder.split_method = options.split_method.unwrap_or(SplitMethod::Sah);
+    let lights = world.lights();
+    let world = builder.build(world);
+
+    let image = cam.render(world, lights);
+
+    match (&options.output, format, sample_layer_type) {
+        (path, _, Some(pixel_type)) => {
+            let layers = [("", &image), ("samples", &cam.sample_counts())];
+            write_exr_layers(&layers, path.as_deref(), pixel_type)?
+        }
+        (Some(path), Some(format), None) => write_image(&image, path, format)?,
+        (None, Some(format), None) => write_to_stdout(&image, format)?,
+        (_, None, None) => write_ppm(&image, &mut BufWriter::new(io::stdout().lock()))?,
+    }
+    if let Some(path) = &options.sample_map {
+        write_image(&cam.sample_map(), path, sample_map_format)?;
+    }
+    Ok(())
+}
+
+fn random_spheres_scene(seed: u64) -> Scene {
+    // The sphere layout is random too, so seed it for a repeatable scene.
+    seed_random(seed);
+
     // World
     let mut world = HittableList::new();
 
     let ground_material =
-        Material::Lambertian(Box::new(Lambertian::new(Color::new_use(0.5, 0.5, 0.5))));
+        Material::Lambertian(Arc::new(Lambertian::new(Color::new_use(0.5, 0.5, 0.5))));
     world.add(Hittable::Sphere(Box::new(Sphere::new_use(
         Point3::new_use(0.0, -1000.0, 0.0),
         1000.0,
@@ -44,7 +194,7 @@ fn main() {
                 if choose_mat < 0.8 {
                     // diffuse
                     let albedo = Color::random() * Color::random();
-                    sphere_material = Material::Lambertian(Box::new(Lambertian::new(albedo)));
+                    sphere_material = Material::Lambertian(Arc::new(Lambertian::new(albedo)));
                     world.add(Hittable::Sphere(Box::new(Sphere::new_use(
                         center,
                         0.2,
@@ -54,7 +204,7 @@ fn main() {
                     // metal
                     let albedo = Color::random_2(0.5, 1.0);
                     let fuzz = random_double_2(0.0, 0.5);
-                    sphere_material = Material::Metal(Box::new(Metal::new(albedo, fuzz)));
+                    sphere_material = Material::Metal(Arc::new(Metal::new(albedo, fuzz)));
                     world.add(Hittable::Sphere(Box::new(Sphere::new_use(
                         center,
                         0.2,
@@ -62,7 +212,7 @@ fn main() {
                     ))));
                 } else {
                     // glass
-                    sphere_material = Material::Dielectric(Box::new(Dielectric::new(1.5)));
+                    sphere_material = Material::Dielectric(Arc::new(Dielectric::new(1.5)));
                     world.add(Hittable::Sphere(Box::new(Sphere::new_use(
                         center,
                         0.2,
@@ -73,21 +223,21 @@ fn main() {
         }
     }
 
-    let material1 = Material::Dielectric(Box::new(Dielectric::new(1.5)));
+    let material1 = Material::Dielectric(Arc::new(Dielectric::new(1.5)));
     world.add(Hittable::Sphere(Box::new(Sphere::new_use(
         Point3::new_use(0.0, 1.0, 0.0),
         1.0,
         material1,
     ))));
 
-    let material2 = Material::Lambertian(Box::new(Lambertian::new(Color::new_use(0.4, 0.2, 0.1))));
+    let material2 = Material::Lambertian(Arc::new(Lambertian::new(Color::new_use(0.4, 0.2, 0.1))));
     world.add(Hittable::Sphere(Box::new(Sphere::new_use(
         Point3::new_use(-4.0, 1.0, 0.0),
         1.0,
         material2,
     ))));
 
-    let material3 = Material::Metal(Box::new(Metal::new(Color::new_use(0.7, 0.6, 0.5), 0.0)));
+    let material3 = Material::Metal(Arc::new(Metal::new(Color::new_use(0.7, 0.6, 0.5), 0.0)));
     world.add(Hittable::Sphere(Box::new(Sphere::new_use(
         Point3::new_use(4.0, 1.0, 0.0),
         1.0,
@@ -106,5 +256,5 @@ fn main() {
     cam.defocus_angle = 0.6;
     cam.focus_dist = 10.0;
 
-    cam.render(Hittable::HittableList(Box::new(world)));
+    Scene { camera: cam, world }
 }
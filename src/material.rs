@@ -1,95 +1,207 @@
 // Material class and ENUMS
 
+use std::f64::consts::PI;
+use std::sync::Arc;
+
 use crate::{
     hittable::HitRecord,
-    ray::Ray,
-    rtweekend::random_double,
-    vec3::{dot, random_unit_vector, reflect, refract, unit_vector, Color, Vec3},
+    ray::{Point3, Ray},
+    texture::Texture,
+    vec3::{dot, reflect, refract, sample_unit_vector, unit_vector, Color, Vec3},
 };
 
+// Variants are reference counted, so a material is shared rather than copied by every object
+// and hit record that uses it.
 #[derive(Clone)]
 pub enum Material {
-    Lambertian(Box<Lambertian>),
-    Metal(Box<Metal>),
-    Dielectric(Box<Dielectric>),
+    Lambertian(Arc<Lambertian>),
+    Metal(Arc<Metal>),
+    Dielectric(Arc<Dielectric>),
+    DiffuseLight(Arc<DiffuseLight>),
+    Isotropic(Arc<Isotropic>),
+}
+
+// A direction drawn by `Material::sample`. `weight` is eval / pdf, the factor the light arriving
+// along `ray` is scaled by. Delta lobes (mirrors, glass) have no density, so `pdf` is zero and
+// `is_delta` is set.
+#[derive(Clone, Copy)]
+pub struct ScatterSample {
+    pub ray: Ray,
+    pub weight: Color,
+    pub pdf: f64,
+    pub is_delta: bool,
 }
 
 impl Material {
-    pub fn scatter(
+    pub fn sample(
         &self,
         r_in: &Ray,
         rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
+        uc: f64,
+        u: [f64; 2],
+    ) -> Option<ScatterSample> {
+        // Picks a direction from the samples `uc` in [0,1) (choosing between lobes) and `u` in
+        // [0,1)^2 (the direction within the lobe). None when the ray is absorbed.
+        match self {
+            Material::Lambertian(l) => l.sample(r_in, rec, u),
+            Material::Metal(m) => m.sample(r_in, rec, u),
+            Material::Dielectric(d) => d.sample(r_in, rec, uc),
+            Material::DiffuseLight(_) => None,
+            Material::Isotropic(i) => i.sample(r_in, rec, u),
+        }
+    }
+
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        // Fraction of the light arriving along `direction` that is scattered back along `r_in`,
+        // per unit solid angle and including the cosine term. Zero for delta lobes.
+        match self {
+            Material::Lambertian(l) => l.eval(r_in, rec, direction),
+            Material::Metal(m) => m.eval(r_in, rec, direction),
+            Material::Isotropic(i) => i.eval(r_in, rec, direction),
+            _ => Color::new_use(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        // Solid-angle density with which `sample` picks `direction`. Zero for delta lobes.
+        match self {
+            Material::Lambertian(l) => l.pdf(r_in, rec, direction),
+            Material::Metal(m) => m.pdf(r_in, rec, direction),
+            Material::Isotropic(i) => i.pdf(r_in, rec, direction),
+            _ => 0.0,
+        }
+    }
+
+    pub fn is_delta(&self) -> bool {
+        // True when every scattered direction comes from a delta lobe, so light sampling can't
+        // help.
         match self {
-            Material::Lambertian(l) => l.scatter(r_in, rec, attenuation, scattered),
-            Material::Metal(m) => m.scatter(r_in, rec, attenuation, scattered),
-            Material::Dielectric(d) => d.scatter(r_in, rec, attenuation, scattered),
+            Material::Metal(m) => m.fuzz == 0.0,
+            Material::Dielectric(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, Material::DiffuseLight(_))
+    }
+
+    pub fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        match self {
+            Material::DiffuseLight(d) => d.emitted(u, v, p),
+            _ => Color::new_use(0.0, 0.0, 0.0),
         }
     }
 
     pub fn new() -> Self {
-        Material::Lambertian(Box::new(Lambertian::new(Color::new())))
+        Material::Lambertian(Arc::new(Lambertian::new(Color::new())))
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Lambertian {
-    albedo: Color,
+    tex: Texture,
 }
 
 impl Lambertian {
     pub fn new(albedo_t: Color) -> Self {
-        Lambertian { albedo: albedo_t }
+        Lambertian {
+            tex: Texture::new_solid(albedo_t),
+        }
     }
 
-    pub fn scatter(
-        &self,
-        _r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        let mut scatter_direction = rec.normal + random_unit_vector();
+    pub fn new_texture(tex_t: Texture) -> Self {
+        Lambertian { tex: tex_t }
+    }
+
+    pub fn sample(&self, r_in: &Ray, rec: &HitRecord, u: [f64; 2]) -> Option<ScatterSample> {
+        let mut scatter_direction = rec.normal + sample_unit_vector(u);
 
         // Catch degenerate scatter direction
         if scatter_direction.near_zero() {
             scatter_direction = rec.normal;
         }
 
-        *scattered = Ray::new_use(&rec.p, &scatter_direction);
-        *attenuation = self.albedo;
-        true
+        Some(ScatterSample {
+            ray: Ray::new_with_time(&rec.p, &scatter_direction, r_in.time()),
+            weight: self.tex.value(rec.u, rec.v, &rec.p),
+            pdf: self.pdf(r_in, rec, scatter_direction),
+            is_delta: false,
+        })
+    }
+
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.pdf(r_in, rec, direction) * self.tex.value(rec.u, rec.v, &rec.p)
+    }
+
+    pub fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        // Cosine-weighted hemisphere around the normal.
+        let cos_theta = dot(rec.normal, unit_vector(direction));
+        if cos_theta < 0.0 {
+            0.0
+        } else {
+            cos_theta / PI
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct Metal {
-    albedo: Color,
+    tex: Texture,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo_t: Color, fuzz_t: f64) -> Self {
+        Self::new_texture(Texture::new_solid(albedo_t), fuzz_t)
+    }
+
+    pub fn new_texture(tex_t: Texture, fuzz_t: f64) -> Self {
         Metal {
-            albedo: albedo_t,
+            tex: tex_t,
             fuzz: (if fuzz_t < 1.0 { fuzz_t } else { 1.0 }),
         }
     }
 
-    pub fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuaion: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
+    pub fn sample(&self, r_in: &Ray, rec: &HitRecord, u: [f64; 2]) -> Option<ScatterSample> {
         let mut reflected = reflect(*r_in.direction(), rec.normal);
-        reflected = unit_vector(reflected) + (self.fuzz * random_unit_vector());
-        *scattered = Ray::new_use(&rec.p, &reflected);
-        *attenuaion = self.albedo;
-        dot(*scattered.direction(), rec.normal) > 0.0
+        reflected = unit_vector(reflected) + (self.fuzz * sample_unit_vector(u));
+        if dot(reflected, rec.normal) <= 0.0 {
+            return None;
+        }
+        Some(ScatterSample {
+            ray: Ray::new_with_time(&rec.p, &reflected, r_in.time()),
+            weight: self.tex.value(rec.u, rec.v, &rec.p),
+            pdf: self.pdf(r_in, rec, reflected),
+            is_delta: self.fuzz == 0.0,
+        })
+    }
+
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.pdf(r_in, rec, direction) * self.tex.value(rec.u, rec.v, &rec.p)
+    }
+
+    pub fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        // Fuzzy reflections pick a uniform point on a sphere of radius `fuzz` around the tip of
+        // the unit mirror direction. Each point the direction passes through on that sphere
+        // contributes t^2 / (4 pi fuzz^2 cos) to the solid-angle density.
+        if self.fuzz == 0.0 || dot(direction, rec.normal) <= 0.0 {
+            return 0.0;
+        }
+        let direction = unit_vector(direction);
+        let center = unit_vector(reflect(*r_in.direction(), rec.normal));
+        let b = dot(direction, center);
+        let discriminant = b * b - (1.0 - self.fuzz * self.fuzz);
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let sqrtd = discriminant.sqrt();
+        let t_squared: f64 = [b - sqrtd, b + sqrtd]
+            .iter()
+            .filter(|&&t| t > 0.0)
+            .map(|t| t * t)
+            .sum();
+        t_squared / (4.0 * PI * self.fuzz * sqrtd)
     }
 }
 
@@ -105,14 +217,7 @@ impl Dielectric {
         }
     }
 
-    pub fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attentuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        *attentuation = Color::new_use(1.0, 1.0, 1.0);
+    pub fn sample(&self, r_in: &Ray, rec: &HitRecord, uc: f64) -> Option<ScatterSample> {
         let ri = if rec.front_face {
             1.0 / self.refraction_index
         } else {
@@ -124,16 +229,18 @@ impl Dielectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = ri * sin_theta > 1.0;
-        let direction: Vec3;
-
-        if cannot_refract || Self::reflectance(&self, cos_theta, ri) > random_double() {
-            direction = reflect(unit_direction, rec.normal);
+        let direction: Vec3 = if cannot_refract || self.reflectance(cos_theta, ri) > uc {
+            reflect(unit_direction, rec.normal)
         } else {
-            direction = refract(unit_direction, rec.normal, ri);
-        }
+            refract(unit_direction, rec.normal, ri)
+        };
 
-        *scattered = Ray::new_use(&rec.p, &direction);
-        true
+        Some(ScatterSample {
+            ray: Ray::new_with_time(&rec.p, &direction, r_in.time()),
+            weight: Color::new_use(1.0, 1.0, 1.0),
+            pdf: 0.0,
+            is_delta: true,
+        })
     }
 
     fn reflectance(&self, cosine: f64, refraction_index: f64) -> f64 {
@@ -143,3 +250,53 @@ impl Dielectric {
         r0 + (1.0 - r0) * f64::powf(1.0 - cosine, 5.0)
     }
 }
+
+#[derive(Clone)]
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit_t: Color) -> Self {
+        DiffuseLight { emit: emit_t }
+    }
+
+    pub fn emitted(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.emit
+    }
+}
+
+// Phase function of a participating medium: scatters uniformly in every direction.
+#[derive(Clone)]
+pub struct Isotropic {
+    tex: Texture,
+}
+
+impl Isotropic {
+    pub fn new(albedo_t: Color) -> Self {
+        Isotropic {
+            tex: Texture::new_solid(albedo_t),
+        }
+    }
+
+    pub fn new_texture(tex_t: Texture) -> Self {
+        Isotropic { tex: tex_t }
+    }
+
+    pub fn sample(&self, r_in: &Ray, rec: &HitRecord, u: [f64; 2]) -> Option<ScatterSample> {
+        Some(ScatterSample {
+            ray: Ray::new_with_time(&rec.p, &sample_unit_vector(u), r_in.time()),
+            weight: self.tex.value(rec.u, rec.v, &rec.p),
+            pdf: 1.0 / (4.0 * PI),
+            is_delta: false,
+        })
+    }
+
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.pdf(r_in, rec, direction) * self.tex.value(rec.u, rec.v, &rec.p)
+    }
+
+    pub fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
@@ -0,0 +1,82 @@
+#![allow(dead_code)]
+use crate::hittable::*;
+use crate::material::Material;
+use crate::ray::Point3;
+use crate::triangle::Triangle;
+use crate::vec3::*;
+
+// Indices of one face corner into the mesh's vertex attribute arrays.
+#[derive(Clone, Copy)]
+pub struct MeshVertex {
+    pub position: usize,
+    pub uv: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+pub struct MeshFace {
+    pub vertices: [MeshVertex; 3],
+    pub material: usize,
+}
+
+// Indexed triangle mesh. Faces share the position, normal and UV arrays and pick their material
+// from `materials` by index.
+#[derive(Clone)]
+pub struct TriangleMesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<[f64; 2]>,
+    pub faces: Vec<MeshFace>,
+    pub materials: Vec<Material>,
+}
+
+impl TriangleMesh {
+    pub fn new() -> Self {
+        TriangleMesh {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            faces: Vec::new(),
+            materials: Vec::new(),
+        }
+    }
+
+    pub fn to_hittable_list(&self) -> HittableList {
+        // Expand the indexed faces into individual triangles.
+        let mut list = HittableList::new();
+
+        for face in &self.faces {
+            let [a, b, c] = face.vertices;
+
+            let normals = match (a.normal, b.normal, c.normal) {
+                (Some(na), Some(nb), Some(nc)) => Some([
+                    unit_vector(self.normals[na]),
+                    unit_vector(self.normals[nb]),
+                    unit_vector(self.normals[nc]),
+                ]),
+                _ => None,
+            };
+            let uvs = match (a.uv, b.uv, c.uv) {
+                (Some(ta), Some(tb), Some(tc)) => Some([self.uvs[ta], self.uvs[tb], self.uvs[tc]]),
+                _ => None,
+            };
+
+            list.add(Hittable::Triangle(Box::new(Triangle::new_shaded(
+                self.positions[a.position],
+                self.positions[b.position],
+                self.positions[c.position],
+                normals,
+                uvs,
+                self.materials[face.material].clone(),
+            ))));
+        }
+
+        list
+    }
+}
+
+impl Default for TriangleMesh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,647 @@
+#![allow(dead_code)]
+// Wavefront OBJ / MTL loader
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::mesh::{MeshFace, MeshVertex, TriangleMesh};
+use crate::ray::Point3;
+use crate::texture::{ImageTexture, Texture, WrapMode};
+use crate::vec3::*;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Texture {
+        path: PathBuf,
+        line: usize,
+        file: PathBuf,
+        source: Box<::image::ImageError>,
+    },
+    // An error in a material library, at the `mtllib` line that included it.
+    Mtl {
+        path: PathBuf,
+        line: usize,
+        source: Box<ObjError>,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            ObjError::Texture {
+                path,
+                line,
+                file,
+                source,
+            } => write!(
+                f,
+                "{}:{}: {}: {}",
+                path.display(),
+                line,
+                file.display(),
+                source
+            ),
+            ObjError::Mtl { path, line, source } => {
+                write!(f, "{}:{}: {}", path.display(), line, source)
+            }
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+            ObjError::Texture { source, .. } => Some(source.as_ref()),
+            ObjError::Mtl { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
+
+// Material definition as read from an MTL file.
+#[derive(Clone)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub kd: Color,
+    pub ks: Color,
+    pub ke: Color,
+    pub ns: f64,
+    pub ni: Option<f64>,
+    pub d: f64,
+    pub illum: i32,
+    pub map_kd: Option<Texture>,
+}
+
+impl MtlMaterial {
+    pub fn new(name: &str) -> Self {
+        MtlMaterial {
+            name: name.to_string(),
+            kd: Color::new_use(0.8, 0.8, 0.8),
+            ks: Color::new(),
+            ke: Color::new(),
+            ns: 0.0,
+            ni: None,
+            d: 1.0,
+            illum: 2,
+            map_kd: None,
+        }
+    }
+
+    pub fn to_material(&self) -> Material {
+        // Emissive colors become lights, transparent or refracting illumination models become
+        // glass, reflective ones (or a purely specular color) become metal, and everything else is
+        // diffuse.
+        let max_component = |c: &Color| f64::max(c.x(), f64::max(c.y(), c.z()));
+
+        if max_component(&self.ke) > 0.0 {
+            Material::DiffuseLight(Arc::new(DiffuseLight::new(self.ke)))
+        } else if self.d < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            let ior = match self.ni {
+                Some(ni) if ni > 1.0 => ni,
+                _ => 1.5,
+            };
+            Material::Dielectric(Arc::new(Dielectric::new(ior)))
+        } else if matches!(self.illum, 3 | 5)
+            || (max_component(&self.ks) > 0.0 && max_component(&self.kd) <= 0.0)
+        {
+            // Phong exponents run from 0 to 1000, with 1000 being a perfect mirror.
+            let fuzz = (1.0 - self.ns / 1000.0).clamp(0.0, 1.0);
+            Material::Metal(Arc::new(Metal::new(self.ks, fuzz)))
+        } else {
+            // A diffuse texture map replaces the diffuse color.
+            let albedo = match &self.map_kd {
+                Some(tex) => tex.clone(),
+                None => Texture::new_solid(self.kd),
+            };
+            Material::Lambertian(Arc::new(Lambertian::new_texture(albedo)))
+        }
+    }
+}
+
+pub fn load_obj(path: &Path) -> Result<TriangleMesh, ObjError> {
+    let source = read_file(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut mesh = TriangleMesh::new();
+    let mut library: HashMap<String, MtlMaterial> = HashMap::new();
+    let mut material_indices: HashMap<String, usize> = HashMap::new();
+    let mut current_material: Option<usize> = None;
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let parse_error = |message: String| ObjError::Parse {
+            path: path.to_path_buf(),
+            line: line_number,
+            message,
+        };
+
+        let mut tokens = strip_comment(raw_line).split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let v = parse_floats(&args, 3, 4).map_err(parse_error)?;
+                mesh.positions.push(Point3::new_use(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let v = parse_floats(&args, 1, 3).map_err(parse_error)?;
+                mesh.uvs.push([v[0], if v.len() > 1 { v[1] } else { 0.0 }]);
+            }
+            "vn" => {
+                let v = parse_floats(&args, 3, 3).map_err(parse_error)?;
+                let normal = Vec3::new_use(v[0], v[1], v[2]);
+                // The mesh normalizes these, which a zero or infinite normal would turn into NaN.
+                let length_squared = normal.length_squared();
+                if length_squared == 0.0 || !length_squared.is_finite() {
+                    return Err(parse_error(format!(
+                        "normal {} {} {} has no direction",
+                        args[0], args[1], args[2]
+                    )));
+                }
+                mesh.normals.push(normal);
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parse_error(format!(
+                        "face needs at least 3 vertices, found {}",
+                        args.len()
+                    )));
+                }
+
+                let mut corners = Vec::with_capacity(args.len());
+                for arg in &args {
+                    corners.push(parse_face_vertex(arg, &mesh).map_err(parse_error)?);
+                }
+
+                // Faces before any `usemtl` get a neutral diffuse material.
+                let material = *current_material
+                    .get_or_insert_with(|| default_material(&mut mesh, &mut material_indices));
+
+                // Triangulate polygons as a fan around the first vertex.
+                for k in 1..(corners.len() - 1) {
+                    mesh.faces.push(MeshFace {
+                        vertices: [corners[0], corners[k], corners[k + 1]],
+                        material,
+                    });
+                }
+            }
+            "mtllib" => {
+                // The file name is the rest of the line, so it may contain spaces.
+                let name = strip_comment(raw_line).trim()[keyword.len()..].trim();
+                if name.is_empty() {
+                    return Err(parse_error("mtllib needs a file name".to_string()));
+                }
+                let materials = load_mtl(&base_dir.join(name)).map_err(|source| ObjError::Mtl {
+                    path: path.to_path_buf(),
+                    line: line_number,
+                    source: Box::new(source),
+                })?;
+                for mtl in materials {
+                    library.insert(mtl.name.clone(), mtl);
+                }
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                let m = match material_indices.get(&name) {
+                    Some(&m) => m,
+                    None => match library.get(&name) {
+                        Some(mtl) => {
+                            let m = mesh.materials.len();
+                            mesh.materials.push(mtl.to_material());
+                            material_indices.insert(name, m);
+                            m
+                        }
+                        None => {
+                            eprintln!(
+                                "warning: {}:{}: unknown material '{}', using a default one",
+                                path.display(),
+                                line_number,
+                                name
+                            );
+                            let m = default_material(&mut mesh, &mut material_indices);
+                            material_indices.insert(name, m);
+                            m
+                        }
+                    },
+                };
+                current_material = Some(m);
+            }
+            // Grouping, smoothing, free-form geometry and anything else we don't render.
+            _ => {}
+        }
+    }
+
+    Ok(mesh)
+}
+
+pub fn load_mtl(path: &Path) -> Result<Vec<MtlMaterial>, ObjError> {
+    let source = read_file(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let parse_error = |message: String| ObjError::Parse {
+            path: path.to_path_buf(),
+            line: line_number,
+            message,
+        };
+
+        let mut tokens = strip_comment(raw_line).split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(parse_error("newmtl needs a material name".to_string()));
+            }
+            materials.push(MtlMaterial::new(&args.join(" ")));
+            continue;
+        }
+
+        let current = match materials.last_mut() {
+            Some(m) => m,
+            None => {
+                return Err(parse_error(format!(
+                    "'{}' appears before any newmtl",
+                    keyword
+                )))
+            }
+        };
+
+        match keyword {
+            "Kd" => current.kd = parse_color(&args).map_err(parse_error)?,
+            "Ks" => current.ks = parse_color(&args).map_err(parse_error)?,
+            "Ke" => current.ke = parse_color(&args).map_err(parse_error)?,
+            "Ns" => current.ns = parse_floats(&args, 1, 1).map_err(parse_error)?[0],
+            "Ni" => current.ni = Some(parse_floats(&args, 1, 1).map_err(parse_error)?[0]),
+            "d" => current.d = parse_floats(&args, 1, 1).map_err(parse_error)?[0],
+            "Tr" => current.d = 1.0 - parse_floats(&args, 1, 1).map_err(parse_error)?[0],
+            "illum" => {
+                current.illum = args
+                    .first()
+                    .and_then(|a| a.parse().ok())
+                    .ok_or_else(|| parse_error("illum needs an integer model".to_string()))?
+            }
+            "map_Kd" => {
+                // Options such as -s or -o come before the file name; we ignore them.
+                let name = args
+                    .last()
+                    .ok_or_else(|| parse_error("map_Kd needs a file name".to_string()))?;
+                let file = base_dir.join(name);
+                let texture = ImageTexture::load(&file, WrapMode::Repeat).map_err(|source| {
+                    ObjError::Texture {
+                        path: path.to_path_buf(),
+                        line: line_number,
+                        file: file.clone(),
+                        source: Box::new(source),
+                    }
+                })?;
+                current.map_kd = Some(Texture::Image(Box::new(texture)));
+            }
+            // Ambient color, other texture maps and statements our materials don't use.
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+fn default_material(
+    mesh: &mut TriangleMesh,
+    material_indices: &mut HashMap<String, usize>,
+) -> usize {
+    // A neutral diffuse material, shared by every face that has no (known) material. It's
+    // registered under the empty name, which `usemtl` can't produce.
+    *material_indices.entry(String::new()).or_insert_with(|| {
+        mesh.materials
+            .push(MtlMaterial::new("default").to_material());
+        mesh.materials.len() - 1
+    })
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find('#') {
+        Some(i) => &line[..i],
+        None => line,
+    }
+}
+
+fn parse_floats(args: &[&str], min: usize, max: usize) -> Result<Vec<f64>, String> {
+    if args.len() < min || args.len() > max {
+        return Err(if min == max {
+            format!("expected {} numbers, found {}", min, args.len())
+        } else {
+            format!("expected {} to {} numbers, found {}", min, max, args.len())
+        });
+    }
+
+    args.iter()
+        .map(|a| {
+            a.parse::<f64>()
+                .map_err(|_| format!("'{}' is not a number", a))
+        })
+        .collect()
+}
+
+fn parse_color(args: &[&str]) -> Result<Color, String> {
+    // A single value means a grey color.
+    let v = parse_floats(args, 1, 3)?;
+    match v.len() {
+        3 => Ok(Color::new_use(v[0], v[1], v[2])),
+        1 => Ok(Color::new_use(v[0], v[0], v[0])),
+        n => Err(format!("expected 1 or 3 color components, found {}", n)),
+    }
+}
+
+fn parse_face_vertex(arg: &str, mesh: &TriangleMesh) -> Result<MeshVertex, String> {
+    // v, v/vt, v//vn or v/vt/vn
+    let mut parts = arg.split('/');
+
+    let position = resolve_index(parts.next(), mesh.positions.len(), "vertex")?
+        .ok_or_else(|| format!("face vertex '{}' has no position index", arg))?;
+    let uv = resolve_index(parts.next(), mesh.uvs.len(), "texture coordinate")?;
+    let normal = resolve_index(parts.next(), mesh.normals.len(), "normal")?;
+
+    if parts.next().is_some() {
+        return Err(format!("malformed face vertex '{}'", arg));
+    }
+
+    Ok(MeshVertex {
+        position,
+        uv,
+        normal,
+    })
+}
+
+fn resolve_index(part: Option<&str>, count: usize, kind: &str) -> Result<Option<usize>, String> {
+    // OBJ indices start at 1; negative indices count back from the latest element.
+    let part = match part {
+        Some(p) if !p.is_empty() => p,
+        _ => return Ok(None),
+    };
+
+    let index: i64 = part
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid {} index", part, kind))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "{} index {} out of range ({} defined)",
+            kind, index, count
+        ));
+    }
+
+    Ok(Some(resolved as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixture {
+        dir: PathBuf,
+        path: PathBuf,
+    }
+
+    impl std::ops::Deref for Fixture {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn write_files(test: &str, files: &[(&str, &str)]) -> Fixture {
+        // Writes `files` into a fresh directory for `test`, removed again when the returned
+        // fixture is dropped. The fixture derefs to the path of the first file.
+        let dir =
+            std::env::temp_dir().join(format!("raytracing-obj-{}-{}", std::process::id(), test));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        let path = dir.join(files[0].0);
+        Fixture { dir, path }
+    }
+
+    fn parse_error_line(result: Result<TriangleMesh, ObjError>) -> (usize, String) {
+        match result {
+            Err(ObjError::Parse { line, message, .. }) => (line, message),
+            Err(e) => panic!("expected a parse error, got '{}'", e),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    const TRIANGLE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+
+    #[test]
+    fn negative_indices_count_back_from_the_latest_vertex() {
+        let obj = format!("{}vn 0 0 1\nf -3//-1 -2//1 -1//-1\n", TRIANGLE);
+        let mesh = load_obj(&write_files("negative", &[("mesh.obj", &obj)])).unwrap();
+        let vertices = mesh.faces[0].vertices;
+        assert_eq!(vertices.map(|v| v.position), [0, 1, 2]);
+        assert_eq!(vertices.map(|v| v.normal), [Some(0); 3]);
+        assert_eq!(vertices.map(|v| v.uv), [None; 3]);
+    }
+
+    #[test]
+    fn polygons_are_triangulated() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+        let mesh = load_obj(&write_files("quad", &[("mesh.obj", obj)])).unwrap();
+        assert_eq!(mesh.faces.len(), 2);
+        assert_eq!(mesh.faces[1].vertices.map(|v| v.position), [0, 2, 3]);
+    }
+
+    #[test]
+    fn malformed_lines_report_their_line() {
+        let cases = [
+            ("v 1 2\n", 1, "expected 3 to 4 numbers, found 2"),
+            ("v 1 2 x\n", 1, "'x' is not a number"),
+            ("v 0 0 0\nvn 0 0 0\n", 2, "normal 0 0 0 has no direction"),
+            ("vn 0 -0.0 0e5\n", 1, "normal 0 -0.0 0e5 has no direction"),
+            ("vn 1 inf 0\n", 1, "normal 1 inf 0 has no direction"),
+            (
+                "v 0 0 0\n\nf 1 1\n",
+                3,
+                "face needs at least 3 vertices, found 2",
+            ),
+            (TRIANGLE, 0, ""),
+        ];
+        for (k, (obj, line, message)) in cases.iter().enumerate() {
+            let path = write_files(&format!("malformed-{}", k), &[("mesh.obj", obj)]);
+            if *line == 0 {
+                assert!(load_obj(&path).is_ok());
+            } else {
+                assert_eq!(
+                    parse_error_line(load_obj(&path)),
+                    (*line, message.to_string())
+                );
+            }
+        }
+
+        let faces = [
+            ("f 1 2 4", "vertex index 4 out of range (3 defined)"),
+            ("f 1 2 -4", "vertex index -4 out of range (3 defined)"),
+            ("f 0 1 2", "vertex index 0 out of range (3 defined)"),
+            (
+                "f 1/1 2 3",
+                "texture coordinate index 1 out of range (0 defined)",
+            ),
+            ("f 1/a 2 3", "'a' is not a valid texture coordinate index"),
+            ("f 1/// 2 3", "malformed face vertex '1///'"),
+            ("f /1 2 3", "face vertex '/1' has no position index"),
+        ];
+        for (k, (face, message)) in faces.iter().enumerate() {
+            let obj = format!("{}{}\n", TRIANGLE, face);
+            let path = write_files(&format!("face-{}", k), &[("mesh.obj", &obj)]);
+            assert_eq!(parse_error_line(load_obj(&path)), (4, message.to_string()));
+        }
+    }
+
+    #[test]
+    fn mtl_statements_map_to_materials() {
+        let mtl = "newmtl lamp\nKe 4 4 4\n\
+                   newmtl glass\nd 0.5\nNi 1.33\n\
+                   newmtl water\nTr 0.25\n\
+                   newmtl mirror\nillum 3\nKs 0.9\nNs 1000\n\
+                   newmtl clay\nKd 0.2 0.3 0.4\n";
+        let materials = load_mtl(&write_files("mapping", &[("lib.mtl", mtl)])).unwrap();
+        let names: Vec<&str> = materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["lamp", "glass", "water", "mirror", "clay"]);
+
+        assert_eq!(materials[0].ke.y(), 4.0);
+        assert!(matches!(
+            materials[0].to_material(),
+            Material::DiffuseLight(_)
+        ));
+        assert_eq!((materials[1].d, materials[1].ni), (0.5, Some(1.33)));
+        assert!(matches!(
+            materials[1].to_material(),
+            Material::Dielectric(_)
+        ));
+        assert_eq!(materials[2].d, 0.75);
+        assert!(matches!(
+            materials[2].to_material(),
+            Material::Dielectric(_)
+        ));
+        assert_eq!(materials[3].ks.x(), 0.9);
+        assert!(matches!(materials[3].to_material(), Material::Metal(_)));
+        assert_eq!(materials[4].kd.z(), 0.4);
+        assert!(matches!(
+            materials[4].to_material(),
+            Material::Lambertian(_)
+        ));
+    }
+
+    #[test]
+    fn mtllib_names_may_contain_spaces() {
+        let obj = format!(
+            "mtllib my materials.mtl # library\n{}usemtl lamp\nf 1 2 3\n",
+            TRIANGLE
+        );
+        let path = write_files(
+            "spaces",
+            &[
+                ("mesh.obj", &obj),
+                ("my materials.mtl", "newmtl lamp\nKe 1 1 1\n"),
+            ],
+        );
+        let mesh = load_obj(&path).unwrap();
+        assert!(matches!(
+            mesh.materials[mesh.faces[0].material],
+            Material::DiffuseLight(_)
+        ));
+    }
+
+    #[test]
+    fn mtllib_errors_name_the_obj_line() {
+        let obj = format!("{}mtllib missing.mtl\n", TRIANGLE);
+        let path = write_files("missing-mtl", &[("mesh.obj", &obj)]);
+        match load_obj(&path) {
+            Err(ObjError::Mtl {
+                path: p,
+                line,
+                source,
+            }) => {
+                assert_eq!((p, line), (path.to_path_buf(), 4));
+                assert!(matches!(*source, ObjError::Io { .. }));
+            }
+            _ => panic!("expected an MTL error"),
+        }
+
+        let obj = "mtllib bad.mtl\n";
+        let path = write_files("bad-mtl", &[("mesh.obj", obj), ("bad.mtl", "Kd 1 1 1\n")]);
+        let message = load_obj(&path).err().unwrap().to_string();
+        assert!(
+            message.starts_with(&format!("{}:1: ", path.display())),
+            "{}",
+            message
+        );
+        assert!(
+            message.ends_with("bad.mtl:1: 'Kd' appears before any newmtl"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn unknown_materials_fall_back_to_the_default() {
+        let obj = format!(
+            "{}f 1 2 3\nusemtl nowhere\nf 1 2 3\nusemtl nowhere\nf 3 2 1\n",
+            TRIANGLE
+        );
+        let mesh = load_obj(&write_files("unknown", &[("mesh.obj", &obj)])).unwrap();
+        assert_eq!(mesh.materials.len(), 1);
+        assert!(mesh.faces.iter().all(|f| f.material == 0));
+        assert!(matches!(mesh.materials[0], Material::Lambertian(_)));
+    }
+}
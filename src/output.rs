@@ -0,0 +1,233 @@
+#![allow(dead_code)]
+// Output stage: turns the linear framebuffer into display values and encodes image files.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::exr::{self, PixelType};
+use crate::hdr;
+use crate::image::Image;
+use crate::interval::Interval;
+use crate::png::{self, BitDepth};
+use crate::vec3::{linear_to_srgb, Color};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    Ppm,
+    Png(BitDepth),
+    Exr(PixelType),
+    Hdr,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        // Picks the format from the file extension. PNG is 8 bits per channel unless the name
+        // ends in ".16.png"; OpenEXR uses half floats.
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" if Path::new(path.file_stem()?).extension() == Some("16".as_ref()) => {
+                Some(ImageFormat::Png(BitDepth::Sixteen))
+            }
+            "png" => Some(ImageFormat::Png(BitDepth::Eight)),
+            "exr" => Some(ImageFormat::Exr(PixelType::Half)),
+            "hdr" => Some(ImageFormat::Hdr),
+            _ => None,
+        }
+    }
+}
+
+pub fn write_image(image: &Image, path: &Path, format: ImageFormat) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_format(image, &mut out, format)
+}
+
+pub fn write_to_stdout(image: &Image, format: ImageFormat) -> io::Result<()> {
+    write_format(image, &mut BufWriter::new(io::stdout().lock()), format)
+}
+
+pub fn write_exr_layers(
+    layers: &[(&str, &Image)],
+    path: Option<&Path>,
+    pixel_type: PixelType,
+) -> io::Result<()> {
+    // Multi-layer EXR to `path`, or to stdout when there's none.
+    match path {
+        Some(path) => write_exr(layers, &mut BufWriter::new(File::create(path)?), pixel_type),
+        None => write_exr(layers, &mut BufWriter::new(io::stdout().lock()), pixel_type),
+    }
+}
+
+fn write_format<W: Write>(image: &Image, out: &mut W, format: ImageFormat) -> io::Result<()> {
+    match format {
+        ImageFormat::Ppm => write_ppm(image, out),
+        ImageFormat::Png(bit_depth) => write_png(image, out, bit_depth),
+        ImageFormat::Exr(pixel_type) => write_exr(&[("", image)], out, pixel_type),
+        ImageFormat::Hdr => write_hdr(image, out),
+    }
+}
+
+pub fn color_to_bytes(pixel_color: Color) -> [u8; 3] {
+    // Same encoding as 8 bit PNG, so every 8 bit format shows the same tones.
+    [0, 1, 2].map(|c| encode_srgb(pixel_color[c], 255.0) as u8)
+}
+
+fn encode_srgb(linear_component: f64, max_value: f64) -> u16 {
+    // sRGB-encodes a linear value and scales [0,1] to the integer range [0, max_value].
+    let intensity: Interval = Interval::new_use(0.0, 1.0);
+    (intensity.clamp(linear_to_srgb(linear_component)) * max_value).round() as u16
+}
+
+pub fn write_ppm<W: Write>(image: &Image, out: &mut W) -> io::Result<()> {
+    // Plain (ASCII) PPM
+    write!(out, "P3\n{} {}\n255\n", image.width(), image.height())?;
+    for pixel_color in image.pixels() {
+        let [r, g, b] = color_to_bytes(*pixel_color);
+        writeln!(out, "{} {} {}", r, g, b)?;
+    }
+    out.flush()
+}
+
+pub fn write_png<W: Write>(image: &Image, out: &mut W, bit_depth: BitDepth) -> io::Result<()> {
+    let max_value = match bit_depth {
+        BitDepth::Eight => 255.0,
+        BitDepth::Sixteen => 65535.0,
+    };
+    let mut samples = Vec::with_capacity(image.pixels().len() * 3);
+    for pixel_color in image.pixels() {
+        for c in 0..3 {
+            samples.push(encode_srgb(pixel_color[c], max_value));
+        }
+    }
+
+    png::write_png(out, image.width(), image.height(), bit_depth, &samples)
+}
+
+pub fn write_exr<W: Write>(
+    layers: &[(&str, &Image)],
+    out: &mut W,
+    pixel_type: PixelType,
+) -> io::Result<()> {
+    // Writes unclamped linear radiance. Each layer becomes R, G and B channels prefixed with the
+    // layer name ("diffuse.R"); an empty name gives the default layer.
+    let (width, height) = match layers.first() {
+        Some((_, image)) => (image.width(), image.height()),
+        None => (0, 0),
+    };
+
+    let mut channels = Vec::with_capacity(layers.len() * 3);
+    for (name, image) in layers {
+        if image.width() != width || image.height() != height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("EXR layer '{}' doesn't match the image size", name),
+            ));
+        }
+
+        for (c, channel_name) in ["R", "G", "B"].iter().enumerate() {
+            let full_name = if name.is_empty() {
+                channel_name.to_string()
+            } else {
+                format!("{}.{}", name, channel_name)
+            };
+            channels.push(exr::Channel {
+                name: full_name,
+                values: image.pixels().iter().map(|p| p[c] as f32).collect(),
+            });
+        }
+    }
+
+    exr::write_exr(out, width, height, pixel_type, &mut channels)
+}
+
+pub fn write_hdr<W: Write>(image: &Image, out: &mut W) -> io::Result<()> {
+    // Writes unclamped linear radiance.
+    hdr::write_hdr(out, image.width(), image.height(), image.pixels())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_from_extension() {
+        let format = |name: &str| ImageFormat::from_path(Path::new(name));
+        assert_eq!(format("out.ppm"), Some(ImageFormat::Ppm));
+        assert_eq!(format("out.PNG"), Some(ImageFormat::Png(BitDepth::Eight)));
+        assert_eq!(
+            format("renders/out.16.png"),
+            Some(ImageFormat::Png(BitDepth::Sixteen))
+        );
+        assert_eq!(
+            format("out.v16.png"),
+            Some(ImageFormat::Png(BitDepth::Eight))
+        );
+        assert_eq!(format("out.exr"), Some(ImageFormat::Exr(PixelType::Half)));
+        assert_eq!(format("out.jpg"), None);
+        assert_eq!(format("out"), None);
+    }
+
+    #[test]
+    fn color_to_bytes_is_srgb_encoded() {
+        assert_eq!(
+            color_to_bytes(Color::new_use(0.0, 0.25, 1.0 / 64.0)),
+            [0, 137, 34]
+        );
+        // The linear segment near black.
+        assert_eq!(color_to_bytes(Color::new_use(0.001, 0.5, 0.0)), [3, 188, 0]);
+    }
+
+    #[test]
+    fn color_to_bytes_clamps_out_of_range_values() {
+        assert_eq!(
+            color_to_bytes(Color::new_use(1.0, 4.0, -2.0)),
+            [255, 255, 0]
+        );
+        assert_eq!(
+            color_to_bytes(Color::new_use(0.999, 1e9, -0.0)),
+            [255, 255, 0]
+        );
+    }
+
+    #[test]
+    fn write_ppm_writes_header_and_rows() {
+        let mut image = Image::new(2, 2);
+        image.set_pixel(0, 0, Color::new_use(1.0, 0.0, 0.0));
+        image.set_pixel(1, 0, Color::new_use(0.0, 1.0, 0.0));
+        image.set_pixel(0, 1, Color::new_use(0.0, 0.0, 1.0));
+        image.set_pixel(1, 1, Color::new_use(0.25, 0.25, 0.25));
+
+        let mut out = Vec::new();
+        write_ppm(&image, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "P3\n2 2\n255\n255 0 0\n0 255 0\n0 0 255\n137 137 137\n"
+        );
+    }
+
+    #[test]
+    fn ppm_and_png_bytes_match() {
+        let mut image = Image::new(4, 3);
+        for j in 0..3 {
+            for i in 0..4 {
+                let v = (i + 4 * j) as f64 / 8.0;
+                image.set_pixel(i, j, Color::new_use(v * v, v, 1.5 - v));
+            }
+        }
+
+        let mut ppm = Vec::new();
+        write_ppm(&image, &mut ppm).unwrap();
+        let ppm_bytes: Vec<u8> = String::from_utf8(ppm)
+            .unwrap()
+            .split_whitespace()
+            .skip(4)
+            .map(|v| v.parse().unwrap())
+            .collect();
+
+        let mut png = Vec::new();
+        write_png(&image, &mut png, BitDepth::Eight).unwrap();
+        let decoded = ::image::load_from_memory(&png).unwrap();
+        assert_eq!(decoded.as_rgb8().unwrap().as_raw(), &ppm_bytes);
+    }
+}
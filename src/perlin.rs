@@ -0,0 +1,117 @@
+#![allow(dead_code)]
+use crate::ray::Point3;
+use crate::rtweekend::Pcg32;
+use crate::vec3::*;
+
+const POINT_COUNT: usize = 256;
+
+// Gradient noise over a 256-cell lattice. The tables come from a PCG32 stream seeded with `seed`,
+// so the same seed always produces the same noise.
+#[derive(Clone)]
+pub struct Perlin {
+    randvec: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Pcg32::new(seed, 0);
+
+        let randvec = (0..POINT_COUNT)
+            .map(|_| {
+                unit_vector(Vec3::new_use(
+                    2.0 * rng.next_f64() - 1.0,
+                    2.0 * rng.next_f64() - 1.0,
+                    2.0 * rng.next_f64() - 1.0,
+                ))
+            })
+            .collect();
+
+        Perlin {
+            randvec,
+            perm_x: Perlin::generate_perm(&mut rng),
+            perm_y: Perlin::generate_perm(&mut rng),
+            perm_z: Perlin::generate_perm(&mut rng),
+        }
+    }
+
+    pub fn noise(&self, p: &Point3) -> f64 {
+        // Returns a value in roughly [-1,1].
+        let u = p.x() - p.x().floor();
+        let v = p.y() - p.y().floor();
+        let w = p.z() - p.z().floor();
+
+        let i = p.x().floor() as i64;
+        let j = p.y().floor() as i64;
+        let k = p.z().floor() as i64;
+
+        let mut c = [[[Vec3::new(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    *corner = self.randvec[self.perm_x[Perlin::wrap(i + di as i64)]
+                        ^ self.perm_y[Perlin::wrap(j + dj as i64)]
+                        ^ self.perm_z[Perlin::wrap(k + dk as i64)]];
+                }
+            }
+        }
+
+        Perlin::perlin_interp(&c, u, v, w)
+    }
+
+    pub fn turb(&self, p: &Point3, depth: i32) -> f64 {
+        // Sum of `depth` octaves, each at double the frequency and half the weight.
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p = 2.0 * temp_p;
+        }
+
+        accum.abs()
+    }
+
+    fn wrap(i: i64) -> usize {
+        (i & (POINT_COUNT as i64 - 1)) as usize
+    }
+
+    fn generate_perm(rng: &mut Pcg32) -> Vec<usize> {
+        let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+
+        // Fisher-Yates shuffle
+        for i in (1..POINT_COUNT).rev() {
+            let target = rng.next_u32() as usize % (i + 1);
+            p.swap(i, target);
+        }
+
+        p
+    }
+
+    fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        // Trilinear interpolation of the corner gradients, with Hermite smoothing of the weights.
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+        let mut accum = 0.0;
+
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, corner) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight_v = Vec3::new_use(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * dot(*corner, weight_v);
+                }
+            }
+        }
+
+        accum
+    }
+}
@@ -0,0 +1,70 @@
+#![allow(dead_code)]
+use crate::aabb::Aabb;
+use crate::hittable::*;
+use crate::interval::{self, *};
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::*;
+use crate::vec3::*;
+
+// Infinite plane through `point`. UVs are distances along two fixed directions in the plane, so
+// a repeating texture tiles once per unit.
+#[derive(Clone)]
+pub struct Plane {
+    point: Point3,
+    normal: Vec3,
+    basis: Onb,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Plane {
+    pub fn new_use(point: Point3, normal: Vec3, mat_t: Material) -> Self {
+        let normal = unit_vector(normal);
+
+        // The box is unbounded, except along the axis an axis-aligned plane is perpendicular to.
+        let mut axes = [interval::UNIVERSAL; 3];
+        for (axis, extent) in axes.iter_mut().enumerate() {
+            let others = (0..3).filter(|&a| a != axis).all(|a| normal[a] == 0.0);
+            if others {
+                *extent = Interval::new_use(point[axis], point[axis]).expand(0.0001);
+            }
+        }
+
+        Plane {
+            point,
+            normal,
+            basis: Onb::new(normal),
+            mat: mat_t,
+            bbox: Aabb::new_use(axes[0], axes[1], axes[2]),
+        }
+    }
+
+    pub fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = dot(self.normal, *r.direction());
+
+        // No hit if the ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = dot(self.normal, self.point - *r.origin()) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = r.at(t);
+        let planar = rec.p - self.point;
+        rec.u = dot(planar, self.basis.u());
+        rec.v = dot(planar, self.basis.v());
+        rec.mat = Some(self.mat.clone());
+        rec.set_face_normal(r, self.normal);
+
+        true
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
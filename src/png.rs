@@ -0,0 +1,75 @@
+#![allow(dead_code)]
+// PNG output through the `image` crate's encoder: 8 or 16 bit RGB.
+
+use std::io::{self, Write};
+
+use ::image::codecs::png::PngEncoder;
+use ::image::{ExtendedColorType, ImageEncoder};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+pub fn write_png<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    bit_depth: BitDepth,
+    samples: &[u16],
+) -> io::Result<()> {
+    // `samples` holds width * height * 3 sRGB-encoded channel values, each in the range of the
+    // chosen bit depth.
+    let (raw, color_type) = match bit_depth {
+        BitDepth::Eight => (
+            samples
+                .iter()
+                .map(|&sample| sample as u8)
+                .collect::<Vec<_>>(),
+            ExtendedColorType::Rgb8,
+        ),
+        // The encoder takes 16 bit samples in native byte order.
+        BitDepth::Sixteen => (
+            samples
+                .iter()
+                .flat_map(|sample| sample.to_ne_bytes())
+                .collect(),
+            ExtendedColorType::Rgb16,
+        ),
+    };
+
+    PngEncoder::new(&mut *out)
+        .write_image(&raw, width as u32, height as u32, color_type)
+        .map_err(io::Error::other)?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::image::{DynamicImage, ImageFormat};
+
+    fn round_trip(bit_depth: BitDepth, samples: &[u16]) -> DynamicImage {
+        let mut out = Vec::new();
+        write_png(&mut out, 2, 2, bit_depth, samples).unwrap();
+        ::image::load_from_memory_with_format(&out, ImageFormat::Png).unwrap()
+    }
+
+    #[test]
+    fn eight_bit_round_trip() {
+        let samples = [0, 1, 2, 64, 128, 255, 10, 20, 30, 255, 254, 253];
+        let decoded = round_trip(BitDepth::Eight, &samples);
+        let expected: Vec<u8> = samples.iter().map(|&s| s as u8).collect();
+        assert_eq!(decoded.as_rgb8().unwrap().as_raw(), &expected);
+    }
+
+    #[test]
+    fn sixteen_bit_round_trip() {
+        let samples = [
+            0, 1, 256, 4660, 32768, 65535, 255, 65280, 43981, 7, 60000, 12345,
+        ];
+        let decoded = round_trip(BitDepth::Sixteen, &samples);
+        assert_eq!(decoded.as_rgb16().unwrap().as_raw(), &samples);
+    }
+}
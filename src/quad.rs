@@ -0,0 +1,184 @@
+#![allow(dead_code)]
+use crate::aabb::Aabb;
+use crate::hittable::*;
+use crate::interval::*;
+use crate::material::Material;
+use crate::ray::*;
+use crate::vec3::*;
+
+// Parallelogram with corner Q and edges u and v. UVs run from 0 to 1 along each edge.
+#[derive(Clone)]
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    mat: Material,
+    bbox: Aabb,
+    normal: Vec3,
+    d: f64,
+    area: f64,
+}
+
+impl Quad {
+    pub fn new_use(q: Point3, u: Vec3, v: Vec3, mat_t: Material) -> Self {
+        let n = cross(u, v);
+        let normal = unit_vector(n);
+
+        // Compute the bounding box of all four vertices.
+        let bbox_diagonal1 = Aabb::new_from_points(q, q + u + v);
+        let bbox_diagonal2 = Aabb::new_from_points(q + u, q + v);
+
+        Quad {
+            q,
+            u,
+            v,
+            w: n / dot(n, n),
+            mat: mat_t,
+            bbox: Aabb::new_enclosing(&bbox_diagonal1, &bbox_diagonal2).padded(),
+            normal,
+            d: dot(normal, q),
+            area: n.length(),
+        }
+    }
+
+    pub fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = dot(self.normal, *r.direction());
+
+        // No hit if the ray is parallel to the plane. The normal has unit length, so `denom`
+        // scales with the ray direction and so does the tolerance.
+        if denom.abs() <= 1e-8 * r.direction().length() {
+            return false;
+        }
+
+        // Return false if the hit point parameter t is outside the ray interval.
+        let t = (self.d - dot(self.normal, *r.origin())) / denom;
+        if !ray_t.contains(t) {
+            return false;
+        }
+
+        // Determine if the hit point lies within the planar shape using its plane coordinates.
+        let intersection = r.at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = dot(self.w, cross(planar_hitpt_vector, self.v));
+        let beta = dot(self.w, cross(self.u, planar_hitpt_vector));
+
+        let unit_interval = Interval::new_use(0.0, 1.0);
+        if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
+            return false;
+        }
+
+        // Ray hits the 2D shape; set the rest of the hit record and return true.
+        rec.t = t;
+        rec.p = intersection;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.mat = Some(self.mat.clone());
+        rec.set_face_normal(r, self.normal);
+
+        true
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.mat
+    }
+
+    pub fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        let mut rec = HitRecord::new();
+        let r = Ray::new_with_time(&origin, &direction, time);
+        if !self.hit(r, Interval::new_use(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = dot(direction, rec.normal).abs() / direction.length();
+
+        distance_squared / (cosine * self.area)
+    }
+
+    pub fn random(&self, origin: Point3, _time: f64, u: [f64; 2]) -> Vec3 {
+        let p = self.q + (u[0] * self.u) + (u[1] * self.v);
+        p - origin
+    }
+}
+
+pub fn make_box(a: Point3, b: Point3, mat: Material) -> HittableList {
+    // Returns the 3D box (six sides) that contains the two opposite vertices a & b.
+    let mut sides = HittableList::new();
+
+    // Construct the two opposite vertices with the minimum and maximum coordinates.
+    let min = Point3::new_use(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::new_use(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vec3::new_use(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new_use(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new_use(0.0, 0.0, max.z() - min.z());
+
+    let mut add = |q: Point3, u: Vec3, v: Vec3| {
+        sides.add(Hittable::Quad(Box::new(Quad::new_use(
+            q,
+            u,
+            v,
+            mat.clone(),
+        ))));
+    };
+
+    add(Point3::new_use(min.x(), min.y(), max.z()), dx, dy); // front
+    add(Point3::new_use(max.x(), min.y(), max.z()), -dz, dy); // right
+    add(Point3::new_use(max.x(), min.y(), min.z()), -dx, dy); // back
+    add(Point3::new_use(min.x(), min.y(), min.z()), dz, dy); // left
+    add(Point3::new_use(min.x(), max.y(), max.z()), dx, -dz); // top
+    add(Point3::new_use(min.x(), min.y(), min.z()), dx, dz); // bottom
+
+    sides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use std::sync::Arc;
+
+    fn quad(size: f64) -> Quad {
+        let mat = Material::Lambertian(Arc::new(Lambertian::new(Color::new_use(0.5, 0.5, 0.5))));
+        Quad::new_use(
+            Point3::new_use(0.0, 0.0, 0.0),
+            Vec3::new_use(size, 0.0, 0.0),
+            Vec3::new_use(0.0, size, 0.0),
+            mat,
+        )
+    }
+
+    fn hit(quad: &Quad, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::new();
+        let r = Ray::new_use(&origin, &direction);
+        quad.hit(r, Interval::new_use(0.0, f64::INFINITY), &mut rec)
+            .then_some(rec)
+    }
+
+    #[test]
+    fn hits_small_and_large_quads() {
+        for size in [1e-9, 1e-6, 1.0, 1e6] {
+            let quad = quad(size);
+            let origin = Point3::new_use(0.25 * size, 0.75 * size, size);
+            // Ray directions aren't normalized, so the direction's length follows the scene too.
+            let rec = hit(&quad, origin, Vec3::new_use(0.0, 0.0, -size))
+                .unwrap_or_else(|| panic!("missed the quad of size {}", size));
+            assert!((rec.t - 1.0).abs() < 1e-9);
+            assert!((rec.u - 0.25).abs() < 1e-9 && (rec.v - 0.75).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn misses_parallel_rays() {
+        for size in [1e-9, 1.0, 1e6] {
+            let quad = quad(size);
+            let origin = Point3::new_use(-size, 0.5 * size, 0.0);
+            assert!(hit(&quad, origin, Vec3::new_use(size, 0.0, 0.0)).is_none());
+        }
+    }
+}
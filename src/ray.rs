@@ -1,3 +1,4 @@
+#![allow(dead_code)]
 use crate::vec3::*;
 
 pub type Point3 = Vec3;
@@ -6,6 +7,7 @@ pub type Point3 = Vec3;
 pub struct Ray {
     dir: Vec3,
     orig: Point3,
+    tm: f64,
 }
 
 impl Ray {
@@ -13,13 +15,19 @@ impl Ray {
         Ray {
             dir: Vec3::new(),
             orig: Point3::new(),
+            tm: 0.0,
         }
     }
 
     pub fn new_use(origin: &Point3, direction: &Vec3) -> Self {
+        Self::new_with_time(origin, direction, 0.0)
+    }
+
+    pub fn new_with_time(origin: &Point3, direction: &Vec3, time: f64) -> Self {
         Ray {
             dir: *direction,
             orig: *origin,
+            tm: time,
         }
     }
 
@@ -31,6 +39,10 @@ impl Ray {
         &self.dir
     }
 
+    pub fn time(&self) -> f64 {
+        self.tm
+    }
+
     pub fn at(&self, t: f64) -> Point3 {
         self.orig + t * self.dir
     }
@@ -1,17 +1,79 @@
 #![allow(dead_code)]
 use core::f64::consts::PI;
-use rand::Rng;
+use std::cell::RefCell;
 
 // Utility functions
 
+thread_local! {
+    // Each thread owns its generator so workers never contend on a shared RNG. The camera reseeds
+    // it for every sample, which keeps renders reproducible however the work is split up.
+    static RNG: RefCell<Pcg32> = RefCell::new(Pcg32::new(0, 0));
+}
+
 pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
 
+pub fn seed_random(seed: u64) {
+    // Reseed the calling thread's generator, making the following draws reproducible.
+    seed_random_stream(seed, 0);
+}
+
+pub fn seed_random_stream(seed: u64, stream: u64) {
+    // Select one of 2^63 independent sequences for the given seed.
+    RNG.with(|rng| *rng.borrow_mut() = Pcg32::new(seed, stream));
+}
+
 pub fn random_double() -> f64 {
-    rand::thread_rng().gen_range(0.0..1.0)
+    // Returns a random real in [0,1).
+    RNG.with(|rng| rng.borrow_mut().next_f64())
 }
 
 pub fn random_double_2(min: f64, max: f64) -> f64 {
-    rand::thread_rng().gen_range(min..max)
+    // Returns a random real in [min,max).
+    min + (max - min) * random_double()
+}
+
+pub fn hash_u64(mut x: u64) -> u64 {
+    // SplitMix64 finalizer: spreads nearby keys (pixel indices, seeds) over the whole range.
+    x = x.wrapping_add(0x9E3779B97F4A7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    x ^ (x >> 31)
+}
+
+// PCG32 (XSH RR) generator, see https://www.pcg-random.org
+#[derive(Clone)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Pcg32 {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(self.inc);
+        let xorshifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rot = (old_state >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        // 53 random bits, the full precision of an f64 mantissa.
+        let bits = ((self.next_u32() as u64) << 32 | self.next_u32() as u64) >> 11;
+        bits as f64 * (1.0 / (1u64 << 53) as f64)
+    }
 }
@@ -0,0 +1,381 @@
+#![allow(dead_code)]
+use crate::rtweekend::{hash_u64, random_double, seed_random_stream};
+
+// Pixel samplers: where in [0,1)^n the samples of a pixel are placed. Every sample of a path asks
+// for its random numbers one dimension (or pair of dimensions) at a time, in the same order, so
+// the low-discrepancy samplers can spread each dimension evenly over the pixel's samples.
+// Distances through participating media are the exception; see `ConstantMedium::hit`.
+
+// How the samples of a pixel are distributed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SamplerType {
+    // Uniform random numbers.
+    Independent,
+    // Jittered strata, shuffled independently in every dimension.
+    Stratified,
+    // Owen-scrambled Halton sequence, one prime base per dimension.
+    Halton,
+    // Owen-scrambled Sobol (0,2)-sequence, shuffled independently for every pair of dimensions.
+    Sobol,
+}
+
+impl SamplerType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "independent" => Some(SamplerType::Independent),
+            "stratified" => Some(SamplerType::Stratified),
+            "halton" => Some(SamplerType::Halton),
+            "sobol" => Some(SamplerType::Sobol),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Sampler {
+    kind: SamplerType,
+    samples_per_pixel: u32,
+    x_strata: u32, // Strata of 2D stratified samples; x_strata * y_strata == samples_per_pixel
+    y_strata: u32,
+    pixel: u64,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl Sampler {
+    pub fn new(kind: SamplerType, samples_per_pixel: i32) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1) as u32;
+        // The most square grid of strata that uses every sample.
+        let mut x_strata = (samples_per_pixel as f64).sqrt() as u32;
+        while !samples_per_pixel.is_multiple_of(x_strata) {
+            x_strata -= 1;
+        }
+        Sampler {
+            kind,
+            samples_per_pixel,
+            x_strata,
+            y_strata: samples_per_pixel / x_strata,
+            pixel: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    pub fn start_pixel_sample(&mut self, pixel: u64, sample_index: i32) {
+        // `pixel` identifies the pixel (and seed); it decorrelates the patterns of neighbouring
+        // pixels. Also reseeds the thread's random stream, which supplies jitter and any random
+        // numbers drawn outside the sampler.
+        self.pixel = pixel;
+        self.sample_index = sample_index as u32;
+        self.dimension = 0;
+        seed_random_stream(pixel, sample_index as u64);
+    }
+
+    pub fn get_1d(&mut self) -> f64 {
+        let hash = self.dimension_hash();
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        match self.kind {
+            SamplerType::Independent => random_double(),
+            SamplerType::Stratified => {
+                let stratum = permutation_element(self.sample_index, self.samples_per_pixel, hash);
+                (stratum as f64 + random_double()) / self.samples_per_pixel as f64
+            }
+            SamplerType::Halton => self.halton(dimension, hash),
+            SamplerType::Sobol => {
+                let index = permutation_element(self.sample_index, self.samples_per_pixel, hash);
+                sobol_sample(index, 0, (hash >> 32) as u32)
+            }
+        }
+    }
+
+    pub fn get_2d(&mut self) -> [f64; 2] {
+        let hash = self.dimension_hash();
+        let dimension = self.dimension;
+        self.dimension += 2;
+
+        match self.kind {
+            SamplerType::Independent => [random_double(), random_double()],
+            SamplerType::Stratified => {
+                let stratum = permutation_element(self.sample_index, self.samples_per_pixel, hash);
+                let x = stratum % self.x_strata;
+                let y = stratum / self.x_strata;
+                [
+                    (x as f64 + random_double()) / self.x_strata as f64,
+                    (y as f64 + random_double()) / self.y_strata as f64,
+                ]
+            }
+            SamplerType::Halton => [
+                self.halton(dimension, hash),
+                self.halton(dimension + 1, hash_u64(hash)),
+            ],
+            SamplerType::Sobol => {
+                let index = permutation_element(self.sample_index, self.samples_per_pixel, hash);
+                [
+                    sobol_sample(index, 0, hash as u32),
+                    sobol_sample(index, 1, (hash >> 32) as u32),
+                ]
+            }
+        }
+    }
+
+    fn dimension_hash(&self) -> u64 {
+        hash_u64(self.pixel ^ hash_u64(self.dimension as u64))
+    }
+
+    fn halton(&self, dimension: u32, hash: u64) -> f64 {
+        // Past the prime table the dimensions fall back to random numbers.
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => owen_scrambled_radical_inverse(
+                base as u64,
+                self.sample_index as u64,
+                self.samples_per_pixel as u64,
+                hash,
+            ),
+            None => random_double(),
+        }
+    }
+}
+
+const PRIME_COUNT: usize = 1000;
+
+// The first PRIME_COUNT primes, the bases of the Halton dimensions.
+static PRIMES: [u32; PRIME_COUNT] = first_primes();
+
+const fn first_primes() -> [u32; PRIME_COUNT] {
+    let mut primes = [0; PRIME_COUNT];
+    let mut count = 0;
+    let mut candidate = 2;
+    while count < PRIME_COUNT {
+        let mut is_prime = true;
+        let mut i = 0;
+        while i < count && primes[i] * primes[i] <= candidate {
+            if candidate % primes[i] == 0 {
+                is_prime = false;
+                break;
+            }
+            i += 1;
+        }
+        if is_prime {
+            primes[count] = candidate;
+            count += 1;
+        }
+        candidate += 1;
+    }
+    primes
+}
+
+fn owen_scrambled_radical_inverse(base: u64, mut index: u64, sample_count: u64, hash: u64) -> f64 {
+    // Mirrors the base-`base` digits of `index` around the radix point, permuting every digit
+    // by a permutation chosen from `hash` and the digits before it. Once the digits that tell
+    // the `sample_count` indices apart are done, the rest are permuted zeros, i.e. uniformly
+    // random, so they're drawn all at once.
+    let inv_base = 1.0 / base as f64;
+    let mut reversed_digits: u64 = 0;
+    let mut inv_base_m = 1.0;
+    let mut span: u64 = 1;
+    while index > 0 || span < sample_count {
+        let next = index / base;
+        let digit = (index - next * base) as u32;
+        let digit_hash = hash_u64(hash ^ reversed_digits);
+        let digit = permutation_element(digit, base as u32, digit_hash);
+        reversed_digits = reversed_digits * base + digit as u64;
+        inv_base_m *= inv_base;
+        span = span.saturating_mul(base);
+        index = next;
+    }
+    let tail = (hash_u64(hash ^ reversed_digits) >> 11) as f64 / (1u64 << 53) as f64;
+    f64::min(
+        inv_base_m * (reversed_digits as f64 + tail),
+        1.0 - f64::EPSILON / 2.0,
+    )
+}
+
+fn sobol_sample(index: u32, dimension: u32, seed: u32) -> f64 {
+    // The first two Sobol dimensions: the van der Corput sequence, and the one whose generator
+    // matrix is Pascal's triangle mod 2. Together they form a (0,2)-sequence.
+    let mut v: u32 = 0;
+    let mut column: u32 = 1 << 31;
+    let mut a = index;
+    while a != 0 {
+        if a & 1 != 0 {
+            v ^= column;
+        }
+        a >>= 1;
+        column = if dimension == 0 {
+            column >> 1
+        } else {
+            column ^ (column >> 1)
+        };
+    }
+    owen_scramble(v, seed) as f64 / 4294967296.0
+}
+
+fn owen_scramble(mut v: u32, seed: u32) -> u32 {
+    // Flips each bit depending on a hash of the bits above it, which keeps the sequence's
+    // stratification while decorrelating it from other pixels and dimensions. With the bits
+    // reversed, a multiply only carries upwards, so each step depends on the higher bits alone
+    // (Laine and Karras, "Stratified Sampling for Stochastic Transparency").
+    v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x05526c56);
+    v ^= v.wrapping_mul(0x53a22864);
+    v.reverse_bits()
+}
+
+fn permutation_element(mut i: u32, l: u32, p: u64) -> u32 {
+    // Element `i` of a random permutation of 0..l selected by `p`, without storing the
+    // permutation (Kensler, "Correlated Multi-Jittered Sampling").
+    let p = p as u32;
+    let mut w = l.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    ((i as u64 + p as u64) % l as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerType; 4] = [
+        SamplerType::Independent,
+        SamplerType::Stratified,
+        SamplerType::Halton,
+        SamplerType::Sobol,
+    ];
+
+    fn pixel_samples(kind: SamplerType, spp: i32, pixel: u64, pair: usize) -> Vec<[f64; 2]> {
+        // The 2D sample at dimension pair `pair` of every sample of the pixel.
+        let mut sampler = Sampler::new(kind, spp);
+        (0..spp)
+            .map(|index| {
+                sampler.start_pixel_sample(pixel, index);
+                for _ in 0..pair {
+                    sampler.get_2d();
+                }
+                sampler.get_2d()
+            })
+            .collect()
+    }
+
+    fn one_per_cell(samples: &[[f64; 2]], x_cells: usize, y_cells: usize) -> bool {
+        let mut seen = vec![false; x_cells * y_cells];
+        for [x, y] in samples {
+            let cell = (y * y_cells as f64) as usize * x_cells + (x * x_cells as f64) as usize;
+            if seen[cell] {
+                return false;
+            }
+            seen[cell] = true;
+        }
+        seen.iter().all(|&s| s)
+    }
+
+    #[test]
+    fn samples_are_in_the_unit_square() {
+        for kind in KINDS {
+            for spp in [1, 7, 16, 64] {
+                let mut sampler = Sampler::new(kind, spp);
+                for pixel in 0..8 {
+                    for index in 0..spp {
+                        sampler.start_pixel_sample(pixel, index);
+                        for _ in 0..20 {
+                            let [x, y] = sampler.get_2d();
+                            let z = sampler.get_1d();
+                            assert!(
+                                [x, y, z].iter().all(|v| (0.0..1.0).contains(v)),
+                                "{:?} gave ({}, {}, {})",
+                                kind,
+                                x,
+                                y,
+                                z
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn power_of_two_counts_are_stratified() {
+        for spp in [4i32, 16, 64, 256] {
+            let k = spp.trailing_zeros();
+            for pixel in 0..4 {
+                for pair in 0..4 {
+                    // Stratified samples fill the grid of strata; Sobol samples fill every
+                    // elementary interval of area 1 / spp.
+                    let samples = pixel_samples(SamplerType::Stratified, spp, pixel, pair);
+                    let side = 1 << (k / 2);
+                    assert!(one_per_cell(&samples, side, spp as usize / side));
+
+                    let samples = pixel_samples(SamplerType::Sobol, spp, pixel, pair);
+                    for a in 0..=k {
+                        assert!(
+                            one_per_cell(&samples, 1 << a, 1 << (k - a)),
+                            "{} samples, {}x{} intervals",
+                            spp,
+                            1 << a,
+                            1 << (k - a)
+                        );
+                    }
+                }
+
+                // The first Halton dimension has base 2.
+                let samples = pixel_samples(SamplerType::Halton, spp, pixel, 0);
+                assert!(one_per_cell(&samples, spp as usize, 1));
+            }
+        }
+    }
+
+    #[test]
+    fn samples_are_deterministic_per_pixel() {
+        for kind in KINDS {
+            let first = pixel_samples(kind, 16, 3, 2);
+            assert!(first == pixel_samples(kind, 16, 3, 2), "{:?}", kind);
+            assert!(first != pixel_samples(kind, 16, 4, 2), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn permutation_element_is_a_permutation() {
+        for l in [1, 2, 3, 7, 16, 100, 1000] {
+            for p in [0, 1, 0x1234_5678_9abc_def0, u64::MAX] {
+                let mut seen = vec![false; l as usize];
+                for i in 0..l {
+                    let element = permutation_element(i, l, p) as usize;
+                    assert!(element < l as usize && !seen[element]);
+                    seen[element] = true;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1149 @@
+#![allow(dead_code)]
+// Declarative TOML scene description
+//
+// [camera]                 Any public `Camera` field; `background`, `integrator`, `sampler` and
+//                          `filter` by name
+// [materials.<name>]       type = "lambertian" | "metal" | "dielectric" | "diffuse_light" |
+//                          "isotropic"
+//                          `albedo` is a color or a texture table: type = "solid" | "checker" |
+//                          "uv_checker" | "image" | "noise" | "turbulence" | "marble"
+// [[objects]]              type = "sphere" | "triangle" | "quad" | "plane" | "disk" | "box" |
+//                          "mesh" | "constant_medium", referencing materials by name; any object
+//                          can have a `transform` list of translate / scale / rotate / euler /
+//                          matrix steps
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use toml::{Table, Value};
+
+use crate::bvh::BvhBuilder;
+use crate::camera::{Background, Camera, Integrator};
+use crate::constant_medium::ConstantMedium;
+use crate::disk::Disk;
+use crate::filter::Filter;
+use crate::hittable::{Hittable, HittableList};
+use crate::instance::Instance;
+use crate::interval::Interval;
+use crate::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+use crate::obj::{self, ObjError};
+use crate::plane::Plane;
+use crate::quad::{self, Quad};
+use crate::ray::Point3;
+use crate::sampler::SamplerType;
+use crate::sphere::Sphere;
+use crate::texture::{
+    CheckerTexture, ImageTexture, MarbleTexture, NoiseTexture, Texture, TurbulenceTexture,
+    UvCheckerTexture, WrapMode,
+};
+use crate::transform::{AnimatedTransform, Mat4, Transform, TransformStep};
+use crate::triangle::Triangle;
+use crate::vec3::*;
+
+pub struct Scene {
+    pub camera: Camera,
+    pub world: HittableList,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Syntax {
+        path: PathBuf,
+        message: String,
+    },
+    Invalid {
+        path: PathBuf,
+        key: String,
+        message: String,
+    },
+    Mesh {
+        path: PathBuf,
+        key: String,
+        source: ObjError,
+    },
+    Texture {
+        path: PathBuf,
+        key: String,
+        source: Box<::image::ImageError>,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Syntax { path, message } => write!(f, "{}: {}", path.display(), message),
+            SceneError::Invalid { path, key, message } => {
+                write!(f, "{}: {}: {}", path.display(), key, message)
+            }
+            SceneError::Mesh { path, key, source } => {
+                write!(f, "{}: {}: {}", path.display(), key, source)
+            }
+            SceneError::Texture { path, key, source } => {
+                write!(f, "{}: {}: {}", path.display(), key, source)
+            }
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Mesh { source, .. } => Some(source),
+            SceneError::Texture { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+pub fn load_scene(path: &Path) -> Result<Scene, SceneError> {
+    let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_scene(&source, path)
+}
+
+pub fn parse_scene(source: &str, path: &Path) -> Result<Scene, SceneError> {
+    // `path` names the scene in error messages; mesh files are resolved relative to it.
+    let root: Table = source
+        .parse()
+        .map_err(|e: toml::de::Error| SceneError::Syntax {
+            path: path.to_path_buf(),
+            message: e.to_string().trim_end().to_string(),
+        })?;
+
+    let parser = Parser {
+        path,
+        base_dir: path.parent().unwrap_or(Path::new("")),
+    };
+    parser.check_keys(&root, "", &["camera", "materials", "objects"])?;
+
+    let camera = match root.get("camera") {
+        Some(value) => parser.parse_camera(parser.table(value, "camera")?)?,
+        None => Camera::new(),
+    };
+
+    let mut materials = HashMap::new();
+    if let Some(value) = root.get("materials") {
+        for (name, definition) in parser.table(value, "materials")? {
+            let key = format!("materials.{}", name);
+            let material = parser.parse_material(parser.table(definition, &key)?, &key)?;
+            materials.insert(name.clone(), material);
+        }
+    }
+
+    let mut world = HittableList::new();
+    if let Some(value) = root.get("objects") {
+        let objects = value
+            .as_array()
+            .ok_or_else(|| parser.invalid("objects", "expected an array of tables"))?;
+        for (i, object) in objects.iter().enumerate() {
+            let key = format!("objects[{}]", i);
+            parser.parse_object(
+                parser.table(object, &key)?,
+                &key,
+                Some(&materials),
+                &mut world,
+            )?;
+        }
+    }
+
+    Ok(Scene { camera, world })
+}
+
+struct Parser<'a> {
+    path: &'a Path,
+    base_dir: &'a Path,
+}
+
+impl Parser<'_> {
+    fn parse_camera(&self, table: &Table) -> Result<Camera, SceneError> {
+        self.check_keys(
+            table,
+            "camera",
+            &[
+                "aspect_ratio",
+                "image_width",
+                "samples_per_pixel",
+                "adaptive_threshold",
+                "adaptive_min_samples",
+                "max_depth",
+                "roulette_depth",
+                "vfov",
+                "lookfrom",
+                "lookat",
+                "vup",
+                "defocus_angle",
+                "focus_dist",
+                "shutter_open",
+                "shutter_close",
+                "background",
+                "integrator",
+                "sampler",
+                "filter",
+                "filter_radius",
+                "seed",
+                "thread_count",
+                "tile_size",
+            ],
+        )?;
+
+        let mut cam = Camera::new();
+        if let Some(v) = self.opt_positive(table, "camera", "aspect_ratio")? {
+            cam.aspect_ratio = v;
+        }
+        if let Some(v) = self.opt_int(table, "camera", "image_width", 1)? {
+            cam.image_width = v as i32;
+        }
+        if let Some(v) = self.opt_int(table, "camera", "samples_per_pixel", 1)? {
+            cam.samples_per_pixel = v as i32;
+        }
+        if let Some(v) = self.opt_float(table, "camera", "adaptive_threshold")? {
+            if v < 0.0 {
+                return Err(self.invalid("camera.adaptive_threshold", "must not be negative"));
+            }
+            cam.adaptive_threshold = v;
+        }
+        if let Some(v) = self.opt_int(table, "camera", "adaptive_min_samples", 1)? {
+            cam.adaptive_min_samples = v as i32;
+        }
+        if let Some(v) = self.opt_int(table, "camera", "max_depth", 1)? {
+            cam.max_depth = v as i32;
+        }
+        if let Some(v) = self.opt_int(table, "camera", "roulette_depth", 0)? {
+            cam.roulette_depth = v as i32;
+        }
+        if let Some(v) = self.opt_float(table, "camera", "vfov")? {
+            if v <= 0.0 || v >= 180.0 {
+                return Err(self.invalid("camera.vfov", "must be between 0 and 180 degrees"));
+            }
+            cam.vfov = v;
+        }
+        if let Some(v) = self.opt_vec3(table, "camera", "lookfrom")? {
+            cam.lookfrom = v;
+        }
+        if let Some(v) = self.opt_vec3(table, "camera", "lookat")? {
+            cam.lookat = v;
+        }
+        if let Some(v) = self.opt_vec3(table, "camera", "vup")? {
+            cam.vup = v;
+        }
+        if let Some(v) = self.opt_float(table, "camera", "defocus_angle")? {
+            if v < 0.0 {
+                return Err(self.invalid("camera.defocus_angle", "must not be negative"));
+            }
+            cam.defocus_angle = v;
+        }
+        if let Some(v) = self.opt_positive(table, "camera", "focus_dist")? {
+            cam.focus_dist = v;
+        }
+        if let Some(v) = self.opt_float(table, "camera", "shutter_open")? {
+            cam.shutter_open = v;
+            cam.shutter_close = v;
+        }
+        if let Some(v) = self.opt_float(table, "camera", "shutter_close")? {
+            if v < cam.shutter_open {
+                return Err(self.invalid(
+                    "camera.shutter_close",
+                    "must not be before camera.shutter_open",
+                ));
+            }
+            cam.shutter_close = v;
+        }
+        if table.contains_key("integrator") {
+            let name = self.req_str(table, "camera", "integrator")?;
+            cam.integrator = Integrator::from_name(name).ok_or_else(|| {
+                self.invalid(
+                    "camera.integrator",
+                    &format!("unknown integrator '{}'", name),
+                )
+            })?;
+        }
+        if table.contains_key("sampler") {
+            let name = self.req_str(table, "camera", "sampler")?;
+            cam.sampler = SamplerType::from_name(name).ok_or_else(|| {
+                self.invalid("camera.sampler", &format!("unknown sampler '{}'", name))
+            })?;
+        }
+        if table.contains_key("filter") {
+            let name = self.req_str(table, "camera", "filter")?;
+            cam.filter = Filter::from_name(name).ok_or_else(|| {
+                self.invalid("camera.filter", &format!("unknown filter '{}'", name))
+            })?;
+        }
+        if let Some(v) = self.opt_positive(table, "camera", "filter_radius")? {
+            cam.filter_radius = v;
+        }
+        if let Some(v) = self.opt_int(table, "camera", "seed", 0)? {
+            cam.seed = v as u64;
+        }
+        if let Some(v) = self.opt_int(table, "camera", "thread_count", 0)? {
+            cam.thread_count = v as usize;
+        }
+        if let Some(v) = self.opt_int(table, "camera", "tile_size", 1)? {
+            cam.tile_size = v as i32;
+        }
+        if let Some(value) = table.get("background") {
+            cam.background = self.parse_background(value)?;
+        }
+
+        if (cam.lookfrom - cam.lookat).near_zero() {
+            return Err(self.invalid("camera.lookat", "must differ from camera.lookfrom"));
+        }
+
+        Ok(cam)
+    }
+
+    fn parse_background(&self, value: &Value) -> Result<Background, SceneError> {
+        // Either "none", or a table: { type = "solid", color = [..] } or
+        // { type = "gradient", bottom = [..], top = [..] }
+        let key = "camera.background";
+        if let Some(s) = value.as_str() {
+            return match s {
+                "none" => Ok(Background::None),
+                _ => Err(self.invalid(key, &format!("unknown background '{}'", s))),
+            };
+        }
+
+        let table = self.table(value, key)?;
+        match self.req_str(table, key, "type")? {
+            "none" => {
+                self.check_keys(table, key, &["type"])?;
+                Ok(Background::None)
+            }
+            "solid" => {
+                self.check_keys(table, key, &["type", "color"])?;
+                Ok(Background::Solid(self.req_vec3(table, key, "color")?))
+            }
+            "gradient" => {
+                self.check_keys(table, key, &["type", "bottom", "top"])?;
+                Ok(Background::Gradient(
+                    self.req_vec3(table, key, "bottom")?,
+                    self.req_vec3(table, key, "top")?,
+                ))
+            }
+            other => Err(self.invalid(
+                &format!("{}.type", key),
+                &format!("unknown background type '{}'", other),
+            )),
+        }
+    }
+
+    fn parse_material(&self, table: &Table, key: &str) -> Result<Material, SceneError> {
+        match self.req_str(table, key, "type")? {
+            "lambertian" => {
+                self.check_keys(table, key, &["type", "albedo"])?;
+                let albedo = self.req_texture(table, key, "albedo")?;
+                Ok(Material::Lambertian(Arc::new(Lambertian::new_texture(
+                    albedo,
+                ))))
+            }
+            "metal" => {
+                self.check_keys(table, key, &["type", "albedo", "fuzz"])?;
+                let albedo = self.req_texture(table, key, "albedo")?;
+                let fuzz = self.opt_float(table, key, "fuzz")?.unwrap_or(0.0);
+                if fuzz < 0.0 {
+                    return Err(self.invalid(&format!("{}.fuzz", key), "must not be negative"));
+                }
+                Ok(Material::Metal(Arc::new(Metal::new_texture(albedo, fuzz))))
+            }
+            "dielectric" => {
+                self.check_keys(table, key, &["type", "refraction_index"])?;
+                let ri = self.req_float(table, key, "refraction_index")?;
+                if ri <= 0.0 {
+                    return Err(
+                        self.invalid(&format!("{}.refraction_index", key), "must be positive")
+                    );
+                }
+                Ok(Material::Dielectric(Arc::new(Dielectric::new(ri))))
+            }
+            "isotropic" => {
+                self.check_keys(table, key, &["type", "albedo"])?;
+                let albedo = self.req_texture(table, key, "albedo")?;
+                Ok(Material::Isotropic(Arc::new(Isotropic::new_texture(
+                    albedo,
+                ))))
+            }
+            "diffuse_light" => {
+                self.check_keys(table, key, &["type", "emit"])?;
+                let emit = self.req_vec3(table, key, "emit")?;
+                Ok(Material::DiffuseLight(Arc::new(DiffuseLight::new(emit))))
+            }
+            other => Err(self.invalid(
+                &format!("{}.type", key),
+                &format!("unknown material type '{}'", other),
+            )),
+        }
+    }
+
+    fn parse_texture(&self, value: &Value, key: &str) -> Result<Texture, SceneError> {
+        // Either a plain [r, g, b] color or a table with a `type`.
+        if value.is_array() {
+            return Ok(Texture::new_solid(self.vec3(value, key)?));
+        }
+
+        let table = self
+            .table(value, key)
+            .map_err(|_| self.invalid(key, "expected a color or a texture table"))?;
+        match self.req_str(table, key, "type")? {
+            "solid" => {
+                self.check_keys(table, key, &["type", "color"])?;
+                Ok(Texture::new_solid(self.req_vec3(table, key, "color")?))
+            }
+            "checker" => {
+                self.check_keys(table, key, &["type", "scale", "even", "odd"])?;
+                let scale = self.req_float(table, key, "scale")?;
+                if scale <= 0.0 {
+                    return Err(self.invalid(&format!("{}.scale", key), "must be positive"));
+                }
+                Ok(Texture::Checker(Box::new(CheckerTexture::new(
+                    scale,
+                    self.req_texture(table, key, "even")?,
+                    self.req_texture(table, key, "odd")?,
+                ))))
+            }
+            "uv_checker" => {
+                self.check_keys(table, key, &["type", "columns", "rows", "even", "odd"])?;
+                let columns = self.opt_positive(table, key, "columns")?.unwrap_or(8.0);
+                let rows = self.opt_positive(table, key, "rows")?.unwrap_or(columns);
+                Ok(Texture::UvChecker(Box::new(UvCheckerTexture::new(
+                    columns,
+                    rows,
+                    self.req_texture(table, key, "even")?,
+                    self.req_texture(table, key, "odd")?,
+                ))))
+            }
+            "image" => {
+                // Image file relative to the scene, with optional wrap modes for each axis.
+                self.check_keys(table, key, &["type", "file", "wrap", "wrap_u", "wrap_v"])?;
+                let file = self.base_dir.join(self.req_str(table, key, "file")?);
+                let wrap = self
+                    .opt_wrap(table, key, "wrap")?
+                    .unwrap_or(WrapMode::Repeat);
+                let wrap_u = self.opt_wrap(table, key, "wrap_u")?.unwrap_or(wrap);
+                let wrap_v = self.opt_wrap(table, key, "wrap_v")?.unwrap_or(wrap);
+
+                let mut image =
+                    ImageTexture::load(&file, wrap).map_err(|source| SceneError::Texture {
+                        path: self.path.to_path_buf(),
+                        key: format!("{}.file", key),
+                        source: Box::new(source),
+                    })?;
+                image.set_wrap(wrap_u, wrap_v);
+                Ok(Texture::Image(Box::new(image)))
+            }
+            "noise" => {
+                self.check_keys(table, key, &["type", "scale", "seed", "color"])?;
+                let (scale, seed, color) = self.noise_params(table, key)?;
+                Ok(Texture::Noise(Box::new(NoiseTexture::new_color(
+                    scale, seed, color,
+                ))))
+            }
+            "turbulence" => {
+                self.check_keys(table, key, &["type", "scale", "depth", "seed", "color"])?;
+                let (scale, seed, color) = self.noise_params(table, key)?;
+                let depth = self.opt_int(table, key, "depth", 1)?.unwrap_or(7) as i32;
+                Ok(Texture::Turbulence(Box::new(TurbulenceTexture::new(
+                    scale, depth, seed, color,
+                ))))
+            }
+            "marble" => {
+                self.check_keys(table, key, &["type", "scale", "depth", "seed", "color"])?;
+                let (scale, seed, color) = self.noise_params(table, key)?;
+                let depth = self.opt_int(table, key, "depth", 1)?.unwrap_or(7) as i32;
+                Ok(Texture::Marble(Box::new(MarbleTexture::new(
+                    scale, depth, seed, color,
+                ))))
+            }
+            other => Err(self.invalid(
+                &format!("{}.type", key),
+                &format!("unknown texture type '{}'", other),
+            )),
+        }
+    }
+
+    fn parse_object(
+        &self,
+        table: &Table,
+        key: &str,
+        materials: Option<&HashMap<String, Material>>,
+        world: &mut HittableList,
+    ) -> Result<(), SceneError> {
+        // `materials` is None for the boundary of a medium, which needs no material.
+        let mut shapes = HittableList::new();
+        match self.req_str(table, key, "type")? {
+            "sphere" => {
+                // A `center_end` makes the sphere move there over the object's `motion_time`.
+                self.check_object_keys(
+                    table,
+                    key,
+                    &["type", "center", "center_end", "radius", "material"],
+                )?;
+                let center = self.req_vec3(table, key, "center")?;
+                let radius = self.req_float(table, key, "radius")?;
+                if radius <= 0.0 {
+                    return Err(self.invalid(&format!("{}.radius", key), "must be positive"));
+                }
+                let mat = self.req_material(table, key, materials)?;
+                let sphere = match self.opt_vec3(table, key, "center_end")? {
+                    Some(center_end) => Sphere::new_moving(
+                        center,
+                        center_end,
+                        self.motion_time(table, key)?,
+                        radius,
+                        mat,
+                    ),
+                    None => Sphere::new_use(center, radius, mat),
+                };
+                shapes.add(Hittable::Sphere(Box::new(sphere)));
+            }
+            "triangle" => {
+                self.check_object_keys(
+                    table,
+                    key,
+                    &["type", "vertices", "normals", "uvs", "material"],
+                )?;
+                let vertices = self.req_vec3_list::<3>(table, key, "vertices")?;
+                let normals = match table.get("normals") {
+                    Some(_) => Some(
+                        self.req_vec3_list::<3>(table, key, "normals")?
+                            .map(unit_vector),
+                    ),
+                    None => None,
+                };
+                let uvs = match table.get("uvs") {
+                    Some(_) => Some(self.req_uv_list(table, key, "uvs")?),
+                    None => None,
+                };
+                let mat = self.req_material(table, key, materials)?;
+                shapes.add(Hittable::Triangle(Box::new(Triangle::new_shaded(
+                    vertices[0],
+                    vertices[1],
+                    vertices[2],
+                    normals,
+                    uvs,
+                    mat,
+                ))));
+            }
+            "quad" => {
+                // Parallelogram with corner `q` and edge vectors `u` and `v`.
+                self.check_object_keys(table, key, &["type", "q", "u", "v", "material"])?;
+                let q = self.req_vec3(table, key, "q")?;
+                let u = self.req_vec3(table, key, "u")?;
+                let v = self.req_vec3(table, key, "v")?;
+                // |u x v| = |u| |v| sin(angle), so compare it to the edge lengths.
+                let n = cross(u, v);
+                if n.length_squared() <= 1e-24 * u.length_squared() * v.length_squared() {
+                    return Err(self.invalid(key, "u and v must not be parallel"));
+                }
+                let mat = self.req_material(table, key, materials)?;
+                shapes.add(Hittable::Quad(Box::new(Quad::new_use(q, u, v, mat))));
+            }
+            "plane" => {
+                self.check_object_keys(table, key, &["type", "point", "normal", "material"])?;
+                let point = self.req_vec3(table, key, "point")?;
+                let normal = self.req_normal(table, key, "normal")?;
+                let mat = self.req_material(table, key, materials)?;
+                shapes.add(Hittable::Plane(Box::new(Plane::new_use(
+                    point, normal, mat,
+                ))));
+            }
+            "disk" => {
+                self.check_object_keys(
+                    table,
+                    key,
+                    &["type", "center", "normal", "radius", "material"],
+                )?;
+                let center = self.req_vec3(table, key, "center")?;
+                let normal = self.req_normal(table, key, "normal")?;
+                let radius = self.req_float(table, key, "radius")?;
+                if radius <= 0.0 {
+                    return Err(self.invalid(&format!("{}.radius", key), "must be positive"));
+                }
+                let mat = self.req_material(table, key, materials)?;
+                shapes.add(Hittable::Disk(Box::new(Disk::new_use(
+                    center, normal, radius, mat,
+                ))));
+            }
+            "box" => {
+                // Axis-aligned box spanning two opposite corners, made of six quads.
+                self.check_object_keys(table, key, &["type", "min", "max", "material"])?;
+                let a = self.req_vec3(table, key, "min")?;
+                let b = self.req_vec3(table, key, "max")?;
+                if (0..3).any(|axis| a[axis] == b[axis]) {
+                    return Err(self.invalid(key, "box must have a non-zero size on every axis"));
+                }
+                let mat = self.req_material(table, key, materials)?;
+                for side in quad::make_box(a, b, mat).into_objects() {
+                    shapes.add(side);
+                }
+            }
+            "constant_medium" => {
+                // Fog or smoke filling the `boundary` object, which is described like any other
+                // object but without a material.
+                self.check_object_keys(table, key, &["type", "boundary", "density", "albedo"])?;
+                let boundary_key = join_key(key, "boundary");
+                let boundary_table = match table.get("boundary") {
+                    Some(value) => self.table(value, &boundary_key)?,
+                    None => return Err(self.invalid(&boundary_key, "missing required key")),
+                };
+                let mut boundary_shapes = HittableList::new();
+                self.parse_object(boundary_table, &boundary_key, None, &mut boundary_shapes)?;
+                let boundary = if boundary_shapes.len() == 1 {
+                    boundary_shapes.into_objects().pop().unwrap()
+                } else {
+                    BvhBuilder::new().build(boundary_shapes)
+                };
+
+                let density = self.req_float(table, key, "density")?;
+                if density <= 0.0 {
+                    return Err(self.invalid(&format!("{}.density", key), "must be positive"));
+                }
+                let albedo = match table.get("albedo") {
+                    Some(_) => self.req_texture(table, key, "albedo")?,
+                    None => Texture::new_solid(Color::new_use(1.0, 1.0, 1.0)),
+                };
+                shapes.add(Hittable::ConstantMedium(Box::new(
+                    ConstantMedium::new_texture(boundary, density, albedo),
+                )));
+            }
+            "mesh" => {
+                // OBJ file relative to the scene. A `material` overrides the MTL materials.
+                self.check_object_keys(table, key, &["type", "file", "material"])?;
+                let file = self.base_dir.join(self.req_str(table, key, "file")?);
+                let mut mesh = obj::load_obj(&file).map_err(|source| SceneError::Mesh {
+                    path: self.path.to_path_buf(),
+                    key: format!("{}.file", key),
+                    source,
+                })?;
+                if table.contains_key("material") {
+                    let mat = self.req_material(table, key, materials)?;
+                    for m in mesh.materials.iter_mut() {
+                        *m = mat.clone();
+                    }
+                }
+                for object in mesh.to_hittable_list().into_objects() {
+                    shapes.add(object);
+                }
+            }
+            other => {
+                return Err(self.invalid(
+                    &format!("{}.type", key),
+                    &format!("unknown object type '{}'", other),
+                ))
+            }
+        }
+
+        // A transformed object becomes one instance around all of its shapes.
+        let animation = match self.opt_transform(table, key)? {
+            Some(animation) => animation,
+            None => {
+                for shape in shapes.into_objects() {
+                    world.add(shape);
+                }
+                return Ok(());
+            }
+        };
+
+        let object = Arc::new(if shapes.len() == 1 {
+            shapes.into_objects().pop().unwrap()
+        } else {
+            BvhBuilder::new().build(shapes)
+        });
+        let instance = if animation.is_animated() {
+            Instance::new_animated(object, animation)
+        } else {
+            Instance::new_use(object, animation.at(0.0))
+        };
+        world.add(Hittable::Instance(Box::new(instance)));
+        Ok(())
+    }
+
+    fn opt_transform(
+        &self,
+        table: &Table,
+        prefix: &str,
+    ) -> Result<Option<AnimatedTransform>, SceneError> {
+        // A list of steps applied in order, each a table with a single key:
+        // { translate = [..] }, { scale = [..] or n }, { rotate = { axis = [..], angle = degrees } },
+        // { euler = [x, y, z] } in degrees, or { matrix = [[..], [..], [..], [..]] } (row-major).
+        // Apart from `matrix`, a step may add `to`, the value it reaches at the end of the
+        // object's `motion_time` (an angle for `rotate`).
+        let key = join_key(prefix, "transform");
+        let steps = match table.get("transform") {
+            None => return Ok(None),
+            Some(value) => value
+                .as_array()
+                .ok_or_else(|| self.invalid(&key, "expected an array of transform steps"))?,
+        };
+
+        let mut animation = AnimatedTransform::new(self.motion_time(table, prefix)?);
+        for (i, step) in steps.iter().enumerate() {
+            let step_key = format!("{}[{}]", key, i);
+            let step_table = self.table(step, &step_key)?;
+            let names: Vec<&String> = step_table.keys().filter(|k| *k != "to").collect();
+            if names.len() != 1 {
+                return Err(self.invalid(&step_key, "expected exactly one transform step"));
+            }
+
+            let name = names[0].as_str();
+            let value = &step_table[name];
+            let value_key = join_key(&step_key, name);
+            let to = step_table.get("to");
+            let to_key = join_key(&step_key, "to");
+            let step = match name {
+                "translate" => {
+                    let from = self.vec3(value, &value_key)?;
+                    let to = match to {
+                        Some(v) => self.vec3(v, &to_key)?,
+                        None => from,
+                    };
+                    TransformStep::Translate(from, to)
+                }
+                "scale" => {
+                    let from = self.scale_factors(value, &value_key)?;
+                    let to = match to {
+                        Some(v) => self.scale_factors(v, &to_key)?,
+                        None => from,
+                    };
+                    TransformStep::Scale(from, to)
+                }
+                "rotate" => {
+                    let rotate = self.table(value, &value_key)?;
+                    self.check_keys(rotate, &value_key, &["axis", "angle"])?;
+                    let axis = self.req_normal(rotate, &value_key, "axis")?;
+                    let from = self.req_float(rotate, &value_key, "angle")?;
+                    let to = match to {
+                        Some(v) => {
+                            as_float(v).ok_or_else(|| self.invalid(&to_key, "expected an angle"))?
+                        }
+                        None => from,
+                    };
+                    TransformStep::Rotate(axis, from, to)
+                }
+                "euler" => {
+                    let from = self.vec3(value, &value_key)?;
+                    let to = match to {
+                        Some(v) => self.vec3(v, &to_key)?,
+                        None => from,
+                    };
+                    TransformStep::Euler(from, to)
+                }
+                "matrix" => {
+                    if to.is_some() {
+                        return Err(self.invalid(&to_key, "a matrix step can't be animated"));
+                    }
+                    let message = "expected 4 rows of 4 numbers";
+                    let rows = value
+                        .as_array()
+                        .filter(|a| a.len() == 4)
+                        .ok_or_else(|| self.invalid(&value_key, message))?;
+                    let mut m = [[0.0; 4]; 4];
+                    for (r, row) in rows.iter().enumerate() {
+                        let v = self.floats(row, &format!("{}[{}]", value_key, r), 4)?;
+                        m[r].copy_from_slice(&v);
+                    }
+                    TransformStep::Fixed(Box::new(
+                        Transform::from_matrix(Mat4::new_use(m))
+                            .ok_or_else(|| self.invalid(&value_key, "matrix is not invertible"))?,
+                    ))
+                }
+                _ => return Err(self.invalid(&value_key, "unknown transform step")),
+            };
+
+            if !animation.push(step) {
+                return Err(self.invalid(&to_key, "scale must not pass through zero"));
+            }
+        }
+
+        Ok(Some(animation))
+    }
+
+    fn scale_factors(&self, value: &Value, key: &str) -> Result<Vec3, SceneError> {
+        // A single number scales uniformly.
+        let factors = match as_float(value) {
+            Some(s) => Vec3::new_use(s, s, s),
+            None => self.vec3(value, key)?,
+        };
+        if (0..3).any(|axis| factors[axis] == 0.0) {
+            return Err(self.invalid(key, "scale must not be zero"));
+        }
+        Ok(factors)
+    }
+
+    // Value helpers. `prefix` is the dotted path of the enclosing table.
+
+    fn invalid(&self, key: &str, message: &str) -> SceneError {
+        SceneError::Invalid {
+            path: self.path.to_path_buf(),
+            key: key.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn check_keys(&self, table: &Table, prefix: &str, allowed: &[&str]) -> Result<(), SceneError> {
+        for key in table.keys() {
+            if !allowed.contains(&key.as_str()) {
+                return Err(self.invalid(&join_key(prefix, key), "unknown key"));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_object_keys(
+        &self,
+        table: &Table,
+        prefix: &str,
+        allowed: &[&str],
+    ) -> Result<(), SceneError> {
+        // Every object type may also carry a transform and the keyframe times of its motion.
+        let mut keys = allowed.to_vec();
+        keys.extend(["transform", "motion_time"]);
+        self.check_keys(table, prefix, &keys)
+    }
+
+    fn motion_time(&self, table: &Table, prefix: &str) -> Result<Interval, SceneError> {
+        // Times at which moving objects are at their start and end; [0, 1] by default.
+        let key = join_key(prefix, "motion_time");
+        match table.get("motion_time") {
+            None => Ok(Interval::new_use(0.0, 1.0)),
+            Some(value) => {
+                let v = self.floats(value, &key, 2)?;
+                if v[1] < v[0] {
+                    return Err(self.invalid(&key, "end must not be before start"));
+                }
+                Ok(Interval::new_use(v[0], v[1]))
+            }
+        }
+    }
+
+    fn table<'t>(&self, value: &'t Value, key: &str) -> Result<&'t Table, SceneError> {
+        value
+            .as_table()
+            .ok_or_else(|| self.invalid(key, "expected a table"))
+    }
+
+    fn opt_float(
+        &self,
+        table: &Table,
+        prefix: &str,
+        name: &str,
+    ) -> Result<Option<f64>, SceneError> {
+        match table.get(name) {
+            None => Ok(None),
+            Some(value) => as_float(value)
+                .map(Some)
+                .ok_or_else(|| self.invalid(&join_key(prefix, name), "expected a number")),
+        }
+    }
+
+    fn req_float(&self, table: &Table, prefix: &str, name: &str) -> Result<f64, SceneError> {
+        self.opt_float(table, prefix, name)?
+            .ok_or_else(|| self.invalid(&join_key(prefix, name), "missing required key"))
+    }
+
+    fn opt_positive(
+        &self,
+        table: &Table,
+        prefix: &str,
+        name: &str,
+    ) -> Result<Option<f64>, SceneError> {
+        match self.opt_float(table, prefix, name)? {
+            Some(v) if v <= 0.0 => Err(self.invalid(&join_key(prefix, name), "must be positive")),
+            v => Ok(v),
+        }
+    }
+
+    fn opt_int(
+        &self,
+        table: &Table,
+        prefix: &str,
+        name: &str,
+        min: i64,
+    ) -> Result<Option<i64>, SceneError> {
+        match table.get(name) {
+            None => Ok(None),
+            Some(value) => {
+                let v = value
+                    .as_integer()
+                    .ok_or_else(|| self.invalid(&join_key(prefix, name), "expected an integer"))?;
+                if v < min || v > i32::MAX as i64 {
+                    return Err(self.invalid(
+                        &join_key(prefix, name),
+                        &format!("must be between {} and {}", min, i32::MAX),
+                    ));
+                }
+                Ok(Some(v))
+            }
+        }
+    }
+
+    fn req_str<'t>(
+        &self,
+        table: &'t Table,
+        prefix: &str,
+        name: &str,
+    ) -> Result<&'t str, SceneError> {
+        match table.get(name) {
+            None => Err(self.invalid(&join_key(prefix, name), "missing required key")),
+            Some(value) => value
+                .as_str()
+                .ok_or_else(|| self.invalid(&join_key(prefix, name), "expected a string")),
+        }
+    }
+
+    fn opt_vec3(
+        &self,
+        table: &Table,
+        prefix: &str,
+        name: &str,
+    ) -> Result<Option<Vec3>, SceneError> {
+        match table.get(name) {
+            None => Ok(None),
+            Some(value) => self.vec3(value, &join_key(prefix, name)).map(Some),
+        }
+    }
+
+    fn req_vec3(&self, table: &Table, prefix: &str, name: &str) -> Result<Vec3, SceneError> {
+        self.opt_vec3(table, prefix, name)?
+            .ok_or_else(|| self.invalid(&join_key(prefix, name), "missing required key"))
+    }
+
+    fn req_normal(&self, table: &Table, prefix: &str, name: &str) -> Result<Vec3, SceneError> {
+        let n = self.req_vec3(table, prefix, name)?;
+        if n.near_zero() {
+            return Err(self.invalid(&join_key(prefix, name), "must not be a zero vector"));
+        }
+        Ok(unit_vector(n))
+    }
+
+    fn vec3(&self, value: &Value, key: &str) -> Result<Vec3, SceneError> {
+        let v = self.floats(value, key, 3)?;
+        Ok(Vec3::new_use(v[0], v[1], v[2]))
+    }
+
+    fn floats(&self, value: &Value, key: &str, count: usize) -> Result<Vec<f64>, SceneError> {
+        let message = format!("expected an array of {} numbers", count);
+        let array = value
+            .as_array()
+            .filter(|a| a.len() == count)
+            .ok_or_else(|| self.invalid(key, &message))?;
+        array
+            .iter()
+            .map(|v| as_float(v).ok_or_else(|| self.invalid(key, &message)))
+            .collect()
+    }
+
+    fn req_vec3_list<const N: usize>(
+        &self,
+        table: &Table,
+        prefix: &str,
+        name: &str,
+    ) -> Result<[Point3; N], SceneError> {
+        let key = join_key(prefix, name);
+        let array = match table.get(name) {
+            None => return Err(self.invalid(&key, "missing required key")),
+            Some(value) => value
+                .as_array()
+                .filter(|a| a.len() == N)
+                .ok_or_else(|| self.invalid(&key, &format!("expected {} points", N)))?,
+        };
+
+        let mut points = [Point3::new(); N];
+        for (i, value) in array.iter().enumerate() {
+            points[i] = self.vec3(value, &format!("{}[{}]", key, i))?;
+        }
+        Ok(points)
+    }
+
+    fn req_uv_list(
+        &self,
+        table: &Table,
+        prefix: &str,
+        name: &str,
+    ) -> Result<[[f64; 2]; 3], SceneError> {
+        let key = join_key(prefix, name);
+        let array = table
+            .get(name)
+            .and_then(|v| v.as_array())
+            .filter(|a| a.len() == 3)
+            .ok_or_else(|| self.invalid(&key, "expected 3 texture coordinates"))?;
+
+        let mut uvs = [[0.0; 2]; 3];
+        for (i, value) in array.iter().enumerate() {
+            let v = self.floats(value, &format!("{}[{}]", key, i), 2)?;
+            uvs[i] = [v[0], v[1]];
+        }
+        Ok(uvs)
+    }
+
+    fn req_texture(&self, table: &Table, prefix: &str, name: &str) -> Result<Texture, SceneError> {
+        match table.get(name) {
+            None => Err(self.invalid(&join_key(prefix, name), "missing required key")),
+            Some(value) => self.parse_texture(value, &join_key(prefix, name)),
+        }
+    }
+
+    fn noise_params(&self, table: &Table, key: &str) -> Result<(f64, u64, Color), SceneError> {
+        // Frequency, noise seed and tint shared by the procedural textures. The seed is fixed
+        // rather than random so that a scene renders the same way every time.
+        let scale = self.opt_positive(table, key, "scale")?.unwrap_or(1.0);
+        let seed = self.opt_int(table, key, "seed", 0)?.unwrap_or(0) as u64;
+        let color = self
+            .opt_vec3(table, key, "color")?
+            .unwrap_or(Color::new_use(1.0, 1.0, 1.0));
+        Ok((scale, seed, color))
+    }
+
+    fn opt_wrap(
+        &self,
+        table: &Table,
+        prefix: &str,
+        name: &str,
+    ) -> Result<Option<WrapMode>, SceneError> {
+        if !table.contains_key(name) {
+            return Ok(None);
+        }
+        let mode = self.req_str(table, prefix, name)?;
+        WrapMode::from_name(mode).map(Some).ok_or_else(|| {
+            self.invalid(
+                &join_key(prefix, name),
+                &format!("unknown wrap mode '{}'", mode),
+            )
+        })
+    }
+
+    fn req_material(
+        &self,
+        table: &Table,
+        prefix: &str,
+        materials: Option<&HashMap<String, Material>>,
+    ) -> Result<Material, SceneError> {
+        let materials = match materials {
+            Some(m) => m,
+            // Medium boundaries have no material, so one would be silently ignored.
+            None if table.contains_key("material") => {
+                return Err(self.invalid(&join_key(prefix, "material"), "unknown key"))
+            }
+            None => return Ok(Material::new()),
+        };
+        let name = self.req_str(table, prefix, "material")?;
+        materials.get(name).cloned().ok_or_else(|| {
+            self.invalid(
+                &join_key(prefix, "material"),
+                &format!("unknown material '{}'", name),
+            )
+        })
+    }
+}
+
+fn join_key(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    // Integers are accepted wherever a number is expected.
+    match value {
+        Value::Float(f) => Some(*f),
+        Value::Integer(i) => Some(*i as f64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_key(source: &str) -> (String, String) {
+        match parse_scene(source, Path::new("test.toml")) {
+            Err(SceneError::Invalid { key, message, .. }) => (key, message),
+            Err(e) => panic!("expected an invalid key, got '{}'", e),
+            Ok(_) => panic!("expected an invalid key"),
+        }
+    }
+
+    const MEDIUM: &str = r#"
+        [materials.white]
+        type = "lambertian"
+        albedo = [0.7, 0.7, 0.7]
+
+        [[objects]]
+        type = "constant_medium"
+        density = 0.01
+        albedo = [1, 1, 1]
+        boundary = { type = "sphere", center = [0, 0, 0], radius = 1 BOUNDARY_EXTRA }
+    "#;
+
+    #[test]
+    fn medium_boundary_takes_no_material() {
+        assert!(parse_scene(
+            &MEDIUM.replace("BOUNDARY_EXTRA", ""),
+            Path::new("test.toml")
+        )
+        .is_ok());
+        assert_eq!(
+            invalid_key(&MEDIUM.replace("BOUNDARY_EXTRA", r#", material = "white""#)),
+            (
+                "objects[0].boundary.material".to_string(),
+                "unknown key".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_object_keys_are_rejected() {
+        let source = MEDIUM.replace("BOUNDARY_EXTRA", ", colour = 1");
+        assert_eq!(
+            invalid_key(&source),
+            (
+                "objects[0].boundary.colour".to_string(),
+                "unknown key".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn quad_edges_are_checked_relative_to_their_length() {
+        let quad = |u: &str, v: &str| {
+            format!(
+                "[materials.white]\ntype = \"lambertian\"\nalbedo = [0.7, 0.7, 0.7]\n\n\
+                 [[objects]]\ntype = \"quad\"\nq = [0, 0, 0]\nu = {}\nv = {}\nmaterial = \"white\"\n",
+                u, v
+            )
+        };
+        for (u, v) in [
+            ("[1e-6, 0, 0]", "[0, 1e-6, 0]"),
+            ("[1e6, 0, 0]", "[0, 1e6, 0]"),
+        ] {
+            assert!(parse_scene(&quad(u, v), Path::new("test.toml")).is_ok());
+        }
+        for (u, v) in [
+            ("[1e6, 0, 0]", "[1e6, 1e-6, 0]"),
+            ("[1, 0, 0]", "[0, 0, 0]"),
+            ("[1e-6, 1e-6, 0]", "[2e-6, 2e-6, 0]"),
+        ] {
+            assert_eq!(
+                invalid_key(&quad(u, v)),
+                (
+                    "objects[0]".to_string(),
+                    "u and v must not be parallel".to_string()
+                )
+            );
+        }
+    }
+}
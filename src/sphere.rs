@@ -1,28 +1,68 @@
 #![allow(dead_code)]
+use crate::aabb::Aabb;
 use crate::hittable::*;
 use crate::interval::*;
 use crate::material::Material;
+use crate::onb::Onb;
 use crate::ray::*;
 use crate::vec3::*;
+use std::f64::consts::PI;
 
 #[derive(Clone)]
 pub struct Sphere {
     center: Point3,
+    motion: Option<(Vec3, Interval)>, // Displacement over the keyframe times, for moving spheres
     radius: f64,
     mat: Material,
+    bbox: Aabb,
 }
 
 impl Sphere {
     pub fn new_use(c: Point3, r: f64, mat_t: Material) -> Self {
+        let rvec = Vec3::new_use(r, r, r);
         Sphere {
             center: c,
+            motion: None,
             radius: r,
             mat: mat_t,
+            bbox: Aabb::new_from_points(c - rvec, c + rvec),
+        }
+    }
+
+    pub fn new_moving(c1: Point3, c2: Point3, time: Interval, r: f64, mat_t: Material) -> Self {
+        // Moves linearly from c1 at time.min to c2 at time.max, and rests at either end outside
+        // that interval, so the box around both ends covers any shutter.
+        let rvec = Vec3::new_use(r, r, r);
+        let box1 = Aabb::new_from_points(c1 - rvec, c1 + rvec);
+        let box2 = Aabb::new_from_points(c2 - rvec, c2 + rvec);
+        Sphere {
+            center: c1,
+            motion: Some((c2 - c1, time)),
+            radius: r,
+            mat: mat_t,
+            bbox: Aabb::new_enclosing(&box1, &box2),
+        }
+    }
+
+    fn center_at(&self, time: f64) -> Point3 {
+        match self.motion {
+            None => self.center,
+            Some((displacement, keyframes)) => {
+                let s = if keyframes.size() > 0.0 {
+                    ((time - keyframes.min) / keyframes.size()).clamp(0.0, 1.0)
+                } else if time < keyframes.min {
+                    0.0
+                } else {
+                    1.0
+                };
+                self.center + s * displacement
+            }
         }
     }
 
     pub fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
-        let oc = self.center - *r.origin();
+        let current_center = self.center_at(r.time());
+        let oc = current_center - *r.origin();
         let a = r.direction().length_squared();
         let h = dot(*r.direction(), oc);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -45,10 +85,80 @@ impl Sphere {
         }
         rec.t = root;
         rec.p = r.at(rec.t);
-        let outward_normal: Vec3 = (rec.p - self.center) / self.radius;
+        let outward_normal: Vec3 = (rec.p - current_center) / self.radius;
         rec.set_face_normal(r, outward_normal);
-        rec.mat = self.mat.clone();
+        (rec.u, rec.v) = Sphere::get_sphere_uv(outward_normal);
+        rec.mat = Some(self.mat.clone());
 
         true
     }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.mat
+    }
+
+    pub fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        // Solid-angle density of `random` choosing `direction` from `origin`.
+        let mut rec = HitRecord::new();
+        let r = Ray::new_with_time(&origin, &direction, time);
+        if !self.hit(r, Interval::new_use(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let dist_squared = (self.center_at(time) - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if dist_squared <= radius_squared {
+            // From inside, points are sampled uniformly over the surface area.
+            let distance = rec.t * direction.length();
+            let cosine = dot(direction, rec.normal).abs() / direction.length();
+            return distance * distance / (cosine * 4.0 * PI * radius_squared);
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / dist_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+
+        1.0 / solid_angle
+    }
+
+    pub fn random(&self, origin: Point3, time: f64, u: [f64; 2]) -> Vec3 {
+        // Direction from `origin` towards a random point of the sphere: uniform within the cone
+        // the sphere subtends, or uniform over the surface from inside.
+        let center = self.center_at(time);
+        let direction = center - origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return center + self.radius * sample_unit_vector(u) - origin;
+        }
+
+        let uvw = Onb::new(direction);
+        uvw.transform(Sphere::random_to_sphere(self.radius, distance_squared, u))
+    }
+
+    fn random_to_sphere(radius: f64, distance_squared: f64, u: [f64; 2]) -> Vec3 {
+        let [r1, r2] = u;
+        let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).sqrt() - 1.0);
+
+        let phi = 2.0 * PI * r1;
+        let x = phi.cos() * (1.0 - z * z).sqrt();
+        let y = phi.sin() * (1.0 - z * z).sqrt();
+
+        Vec3::new_use(x, y, z)
+    }
+
+    fn get_sphere_uv(p: Point3) -> (f64, f64) {
+        // p: a given point on the sphere of radius one, centered at the origin.
+        // u: returned value [0,1] of angle around the Y axis from X=-1.
+        // v: returned value [0,1] of angle from Y=-1 to Y=+1.
+        //     <1 0 0> yields <0.50 0.50>       <-1  0  0> yields <0.00 0.50>
+        //     <0 1 0> yields <0.50 1.00>       < 0 -1  0> yields <0.50 0.00>
+        //     <0 0 1> yields <0.25 0.50>       < 0  0 -1> yields <0.75 0.50>
+        let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+        let phi = f64::atan2(-p.z(), p.x()) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
 }
@@ -0,0 +1,303 @@
+#![allow(dead_code)]
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::perlin::Perlin;
+use crate::ray::Point3;
+use crate::vec3::*;
+
+#[derive(Clone)]
+pub enum Texture {
+    SolidColor(Box<SolidColor>),
+    Checker(Box<CheckerTexture>),
+    UvChecker(Box<UvCheckerTexture>),
+    Image(Box<ImageTexture>),
+    Noise(Box<NoiseTexture>),
+    Turbulence(Box<TurbulenceTexture>),
+    Marble(Box<MarbleTexture>),
+}
+
+impl Texture {
+    pub fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        match self {
+            Texture::SolidColor(s) => s.value(u, v, p),
+            Texture::Checker(c) => c.value(u, v, p),
+            Texture::UvChecker(c) => c.value(u, v, p),
+            Texture::Image(i) => i.value(u, v, p),
+            Texture::Noise(n) => n.value(u, v, p),
+            Texture::Turbulence(t) => t.value(u, v, p),
+            Texture::Marble(m) => m.value(u, v, p),
+        }
+    }
+
+    pub fn new_solid(albedo: Color) -> Self {
+        Texture::SolidColor(Box::new(SolidColor::new(albedo)))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        SolidColor { albedo }
+    }
+
+    pub fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.albedo
+    }
+}
+
+// Checker pattern in world space, made of cubes of size `scale`.
+#[derive(Clone)]
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Texture,
+    odd: Texture,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Texture, odd: Texture) -> Self {
+        CheckerTexture {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+
+    pub fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let x_integer = (self.inv_scale * p.x()).floor() as i64;
+        let y_integer = (self.inv_scale * p.y()).floor() as i64;
+        let z_integer = (self.inv_scale * p.z()).floor() as i64;
+
+        let is_even = (x_integer + y_integer + z_integer) % 2 == 0;
+
+        if is_even {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// Checker pattern in texture space, with `columns` x `rows` squares over the unit UV square.
+#[derive(Clone)]
+pub struct UvCheckerTexture {
+    columns: f64,
+    rows: f64,
+    even: Texture,
+    odd: Texture,
+}
+
+impl UvCheckerTexture {
+    pub fn new(columns: f64, rows: f64, even: Texture, odd: Texture) -> Self {
+        UvCheckerTexture {
+            columns,
+            rows,
+            even,
+            odd,
+        }
+    }
+
+    pub fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let u_integer = (u * self.columns).floor() as i64;
+        let v_integer = (v * self.rows).floor() as i64;
+
+        if (u_integer + v_integer) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// How texture coordinates outside [0,1] are mapped back onto the image.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "repeat" => Some(WrapMode::Repeat),
+            "clamp" => Some(WrapMode::Clamp),
+            "mirror" => Some(WrapMode::Mirror),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, i: i64, n: i64) -> usize {
+        // Maps a texel index onto [0, n).
+        let wrapped = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Mirror => {
+                let period = i.rem_euclid(2 * n);
+                if period < n {
+                    period
+                } else {
+                    2 * n - 1 - period
+                }
+            }
+        };
+        wrapped as usize
+    }
+}
+
+// Bilinearly filtered image. Texels are stored as linear colors and shared between clones.
+#[derive(Clone)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    texels: Arc<Vec<Color>>,
+    wrap_u: WrapMode,
+    wrap_v: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, texels: Vec<Color>, wrap: WrapMode) -> Self {
+        // `texels` holds width * height linear colors, row by row from the top.
+        ImageTexture {
+            width,
+            height,
+            texels: Arc::new(texels),
+            wrap_u: wrap,
+            wrap_v: wrap,
+        }
+    }
+
+    pub fn load(path: &Path, wrap: WrapMode) -> Result<Self, ::image::ImageError> {
+        // Loads a PNG or JPEG file, converting its sRGB values to linear colors.
+        let decoded = ::image::open(path)?.into_rgb32f();
+        let (width, height) = (decoded.width() as usize, decoded.height() as usize);
+
+        let texels = decoded
+            .pixels()
+            .map(|p| {
+                Color::new_use(
+                    srgb_to_linear(p[0] as f64),
+                    srgb_to_linear(p[1] as f64),
+                    srgb_to_linear(p[2] as f64),
+                )
+            })
+            .collect();
+
+        Ok(Self::new(width, height, texels, wrap))
+    }
+
+    pub fn set_wrap(&mut self, wrap_u: WrapMode, wrap_v: WrapMode) {
+        self.wrap_u = wrap_u;
+        self.wrap_v = wrap_v;
+    }
+
+    pub fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        // If we have no texture data, then return solid cyan as a debugging aid.
+        if self.width == 0 || self.height == 0 {
+            return Color::new_use(0.0, 1.0, 1.0);
+        }
+
+        // Texel centers sit at half-integer coordinates. Flip V to image coordinates, where the
+        // first row is at the top.
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        if !x.is_finite() || !y.is_finite() {
+            return Color::new_use(0.0, 1.0, 1.0);
+        }
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let (w, h) = (self.width as i64, self.height as i64);
+        let i0 = self.wrap_u.apply(x0 as i64, w);
+        let i1 = self.wrap_u.apply(x0 as i64 + 1, w);
+        let j0 = self.wrap_v.apply(y0 as i64, h);
+        let j1 = self.wrap_v.apply(y0 as i64 + 1, h);
+
+        let texel = |i: usize, j: usize| self.texels[j * self.width + i];
+        let top = (1.0 - fx) * texel(i0, j0) + fx * texel(i1, j0);
+        let bottom = (1.0 - fx) * texel(i0, j1) + fx * texel(i1, j1);
+        (1.0 - fy) * top + fy * bottom
+    }
+}
+
+// Smooth Perlin noise remapped to [0,1]. The noise tables are shared between clones.
+#[derive(Clone)]
+pub struct NoiseTexture {
+    noise: Arc<Perlin>,
+    scale: f64,
+    albedo: Color,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64, seed: u64) -> Self {
+        Self::new_color(scale, seed, Color::new_use(1.0, 1.0, 1.0))
+    }
+
+    pub fn new_color(scale: f64, seed: u64, albedo: Color) -> Self {
+        NoiseTexture {
+            noise: Arc::new(Perlin::new(seed)),
+            scale,
+            albedo,
+        }
+    }
+
+    pub fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        0.5 * (1.0 + self.noise.noise(&(self.scale * *p))) * self.albedo
+    }
+}
+
+// Sum of `depth` octaves of Perlin noise, giving a rough, cloudy pattern.
+#[derive(Clone)]
+pub struct TurbulenceTexture {
+    noise: Arc<Perlin>,
+    scale: f64,
+    depth: i32,
+    albedo: Color,
+}
+
+impl TurbulenceTexture {
+    pub fn new(scale: f64, depth: i32, seed: u64, albedo: Color) -> Self {
+        TurbulenceTexture {
+            noise: Arc::new(Perlin::new(seed)),
+            scale,
+            depth,
+            albedo,
+        }
+    }
+
+    pub fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        self.noise.turb(&(self.scale * *p), self.depth).min(1.0) * self.albedo
+    }
+}
+
+// Marble-like veins: a sine wave along Z whose phase is disturbed by turbulence.
+#[derive(Clone)]
+pub struct MarbleTexture {
+    noise: Arc<Perlin>,
+    scale: f64,
+    depth: i32,
+    albedo: Color,
+}
+
+impl MarbleTexture {
+    pub fn new(scale: f64, depth: i32, seed: u64, albedo: Color) -> Self {
+        MarbleTexture {
+            noise: Arc::new(Perlin::new(seed)),
+            scale,
+            depth,
+            albedo,
+        }
+    }
+
+    pub fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let phase = self.scale * p.z() + 10.0 * self.noise.turb(p, self.depth);
+        0.5 * (1.0 + phase.sin()) * self.albedo
+    }
+}
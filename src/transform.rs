@@ -0,0 +1,524 @@
+#![allow(dead_code)]
+use std::ops::Mul;
+
+use crate::aabb::{self, Aabb};
+use crate::interval::Interval;
+use crate::ray::Point3;
+use crate::rtweekend::degrees_to_radians;
+use crate::vec3::*;
+
+// Row-major 4x4 matrix acting on column vectors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new() -> Self {
+        Self::identity()
+    }
+
+    pub fn new_use(m: [[f64; 4]; 4]) -> Self {
+        Mat4 { m }
+    }
+
+    pub fn identity() -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Mat4 { m }
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut t = [[0.0; 4]; 4];
+        for (i, row) in t.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4 { m: t }
+    }
+
+    pub fn inverse(&self) -> Option<Mat4> {
+        // Gauss-Jordan elimination with partial pivoting. Returns None for singular matrices.
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&r1, &r2| a[r1][col].abs().total_cmp(&a[r2][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for k in 0..4 {
+                a[col][k] *= scale;
+                inv[col][k] *= scale;
+            }
+
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for k in 0..4 {
+                        a[row][k] -= factor * a[col][k];
+                        inv[row][k] -= factor * inv[col][k];
+                    }
+                }
+            }
+        }
+
+        Some(Mat4 { m: inv })
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        // Affine matrices only; the projective row is ignored.
+        let m = &self.m;
+        Point3::new_use(
+            m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3],
+            m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3],
+            m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3],
+        )
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new_use(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+// Affine transform from object space to world space, kept together with its inverse.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Transform {
+            matrix: Mat4::identity(),
+            inverse: Mat4::identity(),
+        }
+    }
+
+    pub fn from_matrix(matrix: Mat4) -> Option<Self> {
+        // Returns None if the matrix can't be inverted, e.g. a zero scale.
+        matrix
+            .inverse()
+            .map(|inverse| Transform { matrix, inverse })
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        let mut matrix = Mat4::identity();
+        let mut inverse = Mat4::identity();
+        for axis in 0..3 {
+            matrix.m[axis][3] = offset[axis];
+            inverse.m[axis][3] = -offset[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    pub fn scale(factors: Vec3) -> Option<Self> {
+        // Non-uniform scale. Returns None if any factor is zero.
+        if (0..3).any(|axis| factors[axis] == 0.0) {
+            return None;
+        }
+        let mut matrix = Mat4::identity();
+        let mut inverse = Mat4::identity();
+        for axis in 0..3 {
+            matrix.m[axis][axis] = factors[axis];
+            inverse.m[axis][axis] = 1.0 / factors[axis];
+        }
+        Some(Transform { matrix, inverse })
+    }
+
+    pub fn rotate(axis: Vec3, degrees: f64) -> Self {
+        // Counter-clockwise rotation about `axis` when looking down it (Rodrigues' formula).
+        let a = unit_vector(axis);
+        let theta = degrees_to_radians(degrees);
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let t = 1.0 - cos_theta;
+        let (x, y, z) = (a.x(), a.y(), a.z());
+
+        let matrix = Mat4::new_use([
+            [
+                t * x * x + cos_theta,
+                t * x * y - sin_theta * z,
+                t * x * z + sin_theta * y,
+                0.0,
+            ],
+            [
+                t * x * y + sin_theta * z,
+                t * y * y + cos_theta,
+                t * y * z - sin_theta * x,
+                0.0,
+            ],
+            [
+                t * x * z - sin_theta * y,
+                t * y * z + sin_theta * x,
+                t * z * z + cos_theta,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        // Rotations are orthogonal, so the inverse is the transpose.
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    pub fn rotate_euler(degrees: Vec3) -> Self {
+        // Rotates about X, then Y, then Z, by the respective component of `degrees`.
+        Transform::rotate(Vec3::new_use(1.0, 0.0, 0.0), degrees.x())
+            .then(&Transform::rotate(
+                Vec3::new_use(0.0, 1.0, 0.0),
+                degrees.y(),
+            ))
+            .then(&Transform::rotate(
+                Vec3::new_use(0.0, 0.0, 1.0),
+                degrees.z(),
+            ))
+    }
+
+    pub fn then(&self, next: &Transform) -> Transform {
+        // Composition that applies `self` first and `next` second.
+        Transform {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        // Normals transform by the inverse transpose, which keeps them perpendicular to the surface
+        // under non-uniform scale. The result is not normalized.
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    pub fn inverse_point(&self, p: Point3) -> Point3 {
+        self.inverse.transform_point(p)
+    }
+
+    pub fn inverse_vector(&self, v: Vec3) -> Vec3 {
+        self.inverse.transform_vector(v)
+    }
+
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        // Box around the eight transformed corners of `bbox`.
+        if !bbox.is_bounded() {
+            return aabb::UNIVERSE;
+        }
+
+        let mut result = Aabb::new();
+        for i in 0..2 {
+            for j in 0..2 {
+                for k in 0..2 {
+                    let corner = Point3::new_use(
+                        if i == 0 { bbox.x.min } else { bbox.x.max },
+                        if j == 0 { bbox.y.min } else { bbox.y.max },
+                        if k == 0 { bbox.z.min } else { bbox.z.max },
+                    );
+                    let p = self.point(corner);
+                    result = Aabb::new_enclosing(&result, &Aabb::new_from_points(p, p));
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// One step of an animated transform, interpolated between a start and an end value.
+#[derive(Clone)]
+pub enum TransformStep {
+    Fixed(Box<Transform>),
+    Translate(Vec3, Vec3),
+    Scale(Vec3, Vec3),
+    Rotate(Vec3, f64, f64),
+    Euler(Vec3, Vec3),
+}
+
+impl TransformStep {
+    pub fn at(&self, s: f64) -> Transform {
+        // `s` runs from 0 at the start value to 1 at the end value.
+        let lerp = |a: Vec3, b: Vec3| (1.0 - s) * a + s * b;
+        match *self {
+            TransformStep::Fixed(ref t) => **t,
+            TransformStep::Translate(a, b) => Transform::translate(lerp(a, b)),
+            // Validated to keep every factor away from zero, see `AnimatedTransform::push`.
+            TransformStep::Scale(a, b) => Transform::scale(lerp(a, b)).unwrap_or_default(),
+            TransformStep::Rotate(axis, a, b) => Transform::rotate(axis, (1.0 - s) * a + s * b),
+            TransformStep::Euler(a, b) => Transform::rotate_euler(lerp(a, b)),
+        }
+    }
+}
+
+// Sequence of steps whose values move from start to end over the keyframe `time` interval, and
+// hold still outside it.
+#[derive(Clone)]
+pub struct AnimatedTransform {
+    steps: Vec<TransformStep>,
+    time: Interval,
+}
+
+impl AnimatedTransform {
+    pub fn new(time: Interval) -> Self {
+        AnimatedTransform {
+            steps: Vec::new(),
+            time,
+        }
+    }
+
+    pub fn push(&mut self, step: TransformStep) -> bool {
+        // Appends a step applied after the existing ones. Returns false for a scale whose factors
+        // pass through zero, which can't be inverted.
+        if let TransformStep::Scale(a, b) = &step {
+            if (0..3).any(|axis| a[axis] * b[axis] <= 0.0) {
+                return false;
+            }
+        }
+        self.steps.push(step);
+        true
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        let s = if self.time.size() > 0.0 {
+            ((time - self.time.min) / self.time.size()).clamp(0.0, 1.0)
+        } else if time < self.time.min {
+            0.0
+        } else {
+            1.0
+        };
+
+        self.steps
+            .iter()
+            .fold(Transform::new(), |t, step| t.then(&step.at(s)))
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.steps.iter().any(|step| step.at(0.0) != step.at(1.0))
+    }
+
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        // Box around the object over the whole animation. The transform is sampled along the
+        // keyframe interval, and the result grows by the furthest any corner moves between two
+        // samples, which covers the curved paths of rotating corners.
+        if !bbox.is_bounded() {
+            return aabb::UNIVERSE;
+        }
+
+        const SAMPLES: usize = 64;
+        let corners: Vec<Point3> = (0..8)
+            .map(|c| {
+                Point3::new_use(
+                    if c & 1 == 0 { bbox.x.min } else { bbox.x.max },
+                    if c & 2 == 0 { bbox.y.min } else { bbox.y.max },
+                    if c & 4 == 0 { bbox.z.min } else { bbox.z.max },
+                )
+            })
+            .collect();
+
+        let mut result = Aabb::new();
+        let mut previous: Option<Vec<Point3>> = None;
+        let mut max_step: f64 = 0.0;
+        for i in 0..=SAMPLES {
+            let t = self.time.min + self.time.size() * i as f64 / SAMPLES as f64;
+            let transform = self.at(t);
+            let points: Vec<Point3> = corners.iter().map(|&c| transform.point(c)).collect();
+
+            for p in &points {
+                result = Aabb::new_enclosing(&result, &Aabb::new_from_points(*p, *p));
+            }
+            if let Some(previous) = &previous {
+                for (a, b) in previous.iter().zip(&points) {
+                    max_step = max_step.max((*b - *a).length());
+                }
+            }
+            previous = Some(points);
+        }
+
+        Aabb::new_use(
+            result.x.expand(max_step),
+            result.y.expand(max_step),
+            result.z.expand(max_step),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(
+            (a - b).length() < 1e-9,
+            "({}, {}, {}) != ({}, {}, {})",
+            a.x(),
+            a.y(),
+            a.z(),
+            b.x(),
+            b.y(),
+            b.z()
+        );
+    }
+
+    fn general_transform() -> Transform {
+        Transform::scale(Vec3::new_use(2.0, 0.5, -3.0))
+            .unwrap()
+            .then(&Transform::rotate(Vec3::new_use(1.0, 2.0, 3.0), 40.0))
+            .then(&Transform::translate(Vec3::new_use(4.0, -5.0, 6.0)))
+    }
+
+    #[test]
+    fn inverse_point_undoes_point() {
+        let transform = general_transform();
+        let from_matrix = Transform::from_matrix(*transform.matrix()).unwrap();
+        for p in [
+            Point3::new_use(0.0, 0.0, 0.0),
+            Point3::new_use(1.0, -2.0, 3.5),
+            Point3::new_use(-100.0, 7.0, 0.25),
+        ] {
+            assert_close(transform.inverse_point(transform.point(p)), p);
+            assert_close(from_matrix.inverse_point(transform.point(p)), p);
+            assert_close(transform.inverse().point(transform.point(p)), p);
+        }
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert!(Transform::scale(Vec3::new_use(1.0, 0.0, 2.0)).is_none());
+        assert!(Transform::scale(Vec3::new_use(-0.0, 1.0, 1.0)).is_none());
+
+        let mut m = Mat4::identity();
+        m.m[2][2] = 1e-13;
+        assert!(m.inverse().is_none());
+        assert!(Transform::from_matrix(m).is_none());
+        m.m[2][2] = 1e-11;
+        assert!(m.inverse().is_some());
+
+        // Two equal rows.
+        let mut m = Mat4::identity();
+        m.m[1] = m.m[0];
+        assert!(m.inverse().is_none());
+    }
+
+    #[test]
+    fn then_applies_self_first() {
+        let scale = Transform::scale(Vec3::new_use(2.0, 2.0, 2.0)).unwrap();
+        let translate = Transform::translate(Vec3::new_use(1.0, 0.0, 0.0));
+        let p = Point3::new_use(1.0, 1.0, 1.0);
+
+        assert_close(
+            scale.then(&translate).point(p),
+            Point3::new_use(3.0, 2.0, 2.0),
+        );
+        assert_close(
+            translate.then(&scale).point(p),
+            Point3::new_use(4.0, 2.0, 2.0),
+        );
+
+        let transform = general_transform();
+        let composed = transform.then(&scale).then(&translate);
+        assert_close(
+            composed.point(p),
+            translate.point(scale.point(transform.point(p))),
+        );
+        assert_close(composed.inverse_point(composed.point(p)), p);
+    }
+
+    #[test]
+    fn animated_bounding_box_contains_the_box_at_every_time() {
+        let mut animated = AnimatedTransform::new(Interval::new_use(0.0, 1.0));
+        assert!(animated.push(TransformStep::Scale(
+            Vec3::new_use(1.0, 1.0, 1.0),
+            Vec3::new_use(2.0, 0.5, 1.0)
+        )));
+        assert!(animated.push(TransformStep::Rotate(
+            Vec3::new_use(0.0, 1.0, 0.0),
+            0.0,
+            270.0
+        )));
+        assert!(animated.push(TransformStep::Translate(
+            Vec3::new_use(0.0, 0.0, 0.0),
+            Vec3::new_use(3.0, 1.0, 0.0)
+        )));
+        assert!(!animated.push(TransformStep::Scale(
+            Vec3::new_use(1.0, 1.0, 1.0),
+            Vec3::new_use(-1.0, 1.0, 1.0)
+        )));
+
+        let bbox = Aabb::new_from_points(
+            Point3::new_use(1.0, -0.5, -0.25),
+            Point3::new_use(2.0, 0.5, 0.25),
+        );
+        let animated_bbox = animated.bounding_box(&bbox);
+        // Times between the bounding box samples, and outside the keyframe interval.
+        for i in -10..=1010 {
+            let transformed = animated.at(i as f64 / 1000.0).bounding_box(&bbox);
+            for axis in 0..3 {
+                let outer = animated_bbox.axis_interval(axis);
+                let inner = transformed.axis_interval(axis);
+                assert!(
+                    outer.min <= inner.min && inner.max <= outer.max,
+                    "time {} axis {}",
+                    i as f64 / 1000.0,
+                    axis
+                );
+            }
+        }
+    }
+}
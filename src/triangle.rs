@@ -0,0 +1,189 @@
+#![allow(dead_code)]
+use crate::aabb::Aabb;
+use crate::hittable::*;
+use crate::interval::*;
+use crate::material::Material;
+use crate::ray::*;
+use crate::vec3::*;
+
+#[derive(Clone)]
+pub struct Triangle {
+    v0: Point3,
+    v1: Point3,
+    v2: Point3,
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[[f64; 2]; 3]>,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Triangle {
+    pub fn new_use(v0: Point3, v1: Point3, v2: Point3, mat_t: Material) -> Self {
+        Self::new_shaded(v0, v1, v2, None, None, mat_t)
+    }
+
+    pub fn new_shaded(
+        v0: Point3,
+        v1: Point3,
+        v2: Point3,
+        normals: Option<[Vec3; 3]>,
+        uvs: Option<[[f64; 2]; 3]>,
+        mat_t: Material,
+    ) -> Self {
+        // Optional per-vertex shading normals and texture coordinates, in vertex order.
+        let bbox = Aabb::new_enclosing(
+            &Aabb::new_from_points(v0, v1),
+            &Aabb::new_from_points(v2, v2),
+        )
+        .padded();
+
+        Triangle {
+            v0,
+            v1,
+            v2,
+            normals,
+            uvs,
+            mat: mat_t,
+            bbox,
+        }
+    }
+
+    pub fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        // Möller–Trumbore ray/triangle intersection
+        let edge1 = self.v1 - self.v0;
+        let edge2 = self.v2 - self.v0;
+        let pvec = cross(*r.direction(), edge2);
+        let det = dot(edge1, pvec);
+
+        // The ray is parallel to the triangle's plane. `det` scales with the lengths of the ray
+        // direction and both edges, so the tolerance does too.
+        let scale =
+            r.direction().length_squared() * edge1.length_squared() * edge2.length_squared();
+        if det * det <= 1e-24 * scale {
+            return false;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = *r.origin() - self.v0;
+        let b1 = dot(tvec, pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return false;
+        }
+
+        let qvec = cross(tvec, edge1);
+        let b2 = dot(*r.direction(), qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return false;
+        }
+
+        let t = dot(edge2, qvec) * inv_det;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        let b0 = 1.0 - b1 - b2;
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.barycentric = Vec3::new_use(b0, b1, b2);
+        rec.set_face_normal(r, unit_vector(cross(edge1, edge2)));
+
+        if let Some(n) = &self.normals {
+            let shading_normal = b0 * n[0] + b1 * n[1] + b2 * n[2];
+            if !shading_normal.near_zero() {
+                rec.set_shading_normal(unit_vector(shading_normal));
+            }
+        }
+
+        match &self.uvs {
+            Some(uv) => {
+                rec.u = b0 * uv[0][0] + b1 * uv[1][0] + b2 * uv[2][0];
+                rec.v = b0 * uv[0][1] + b1 * uv[1][1] + b2 * uv[2][1];
+            }
+            None => {
+                rec.u = b1;
+                rec.v = b2;
+            }
+        }
+
+        rec.mat = Some(self.mat.clone());
+
+        true
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.mat
+    }
+
+    pub fn pdf_value(&self, origin: Point3, direction: Vec3, time: f64) -> f64 {
+        let mut rec = HitRecord::new();
+        let r = Ray::new_with_time(&origin, &direction, time);
+        if !self.hit(r, Interval::new_use(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let area = 0.5 * cross(self.v1 - self.v0, self.v2 - self.v0).length();
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = dot(direction, rec.geometric_normal).abs() / direction.length();
+
+        distance_squared / (cosine * area)
+    }
+
+    pub fn random(&self, origin: Point3, _time: f64, u: [f64; 2]) -> Vec3 {
+        // Uniform over the area, by folding a uniform square sample onto barycentric coordinates.
+        let su = u[0].sqrt();
+        let b1 = 1.0 - su;
+        let b2 = u[1] * su;
+        let p = self.v0 + b1 * (self.v1 - self.v0) + b2 * (self.v2 - self.v0);
+        p - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use std::sync::Arc;
+
+    fn triangle(size: f64) -> Triangle {
+        let mat = Material::Lambertian(Arc::new(Lambertian::new(Color::new_use(0.5, 0.5, 0.5))));
+        Triangle::new_use(
+            Point3::new_use(0.0, 0.0, 0.0),
+            Point3::new_use(size, 0.0, 0.0),
+            Point3::new_use(0.0, size, 0.0),
+            mat,
+        )
+    }
+
+    fn hit(triangle: &Triangle, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+        let mut rec = HitRecord::new();
+        let r = Ray::new_use(&origin, &direction);
+        triangle
+            .hit(r, Interval::new_use(0.0, f64::INFINITY), &mut rec)
+            .then_some(rec)
+    }
+
+    #[test]
+    fn hits_tiny_triangles() {
+        for size in [1e-3, 1e-6, 1e-9] {
+            let tri = triangle(size);
+            let origin = Point3::new_use(0.25 * size, 0.25 * size, size);
+            let rec = hit(&tri, origin, Vec3::new_use(0.0, 0.0, -1.0))
+                .unwrap_or_else(|| panic!("missed the triangle of size {}", size));
+            assert!((rec.t - size).abs() < 1e-9 * size);
+            assert!((rec.barycentric.x() - 0.5).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn misses_parallel_rays() {
+        for size in [1e-6, 1.0, 1e6] {
+            let tri = triangle(size);
+            let origin = Point3::new_use(-size, 0.25 * size, 0.0);
+            assert!(hit(&tri, origin, Vec3::new_use(1.0, 0.0, 0.0)).is_none());
+        }
+    }
+}
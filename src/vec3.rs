@@ -2,10 +2,7 @@
 use core::fmt;
 use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub};
 
-use crate::{
-    interval::Interval,
-    rtweekend::{random_double, random_double_2},
-};
+use crate::rtweekend::{random_double, random_double_2};
 
 #[derive(Clone, Copy)]
 pub struct Vec3 {
@@ -228,6 +225,31 @@ pub fn random_in_unit_disk() -> Vec3 {
     }
 }
 
+pub fn sample_unit_vector(u: [f64; 2]) -> Vec3 {
+    // Maps a point of the unit square uniformly onto the unit sphere.
+    let z = 1.0 - 2.0 * u[0];
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * std::f64::consts::PI * u[1];
+    Vec3::new_use(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn sample_in_unit_disk(u: [f64; 2]) -> Vec3 {
+    // Maps a point of the unit square uniformly onto the unit disk (Shirley's concentric mapping,
+    // which keeps strata compact).
+    let x = 2.0 * u[0] - 1.0;
+    let y = 2.0 * u[1] - 1.0;
+    if x == 0.0 && y == 0.0 {
+        return Vec3::new();
+    }
+    let quarter_pi = std::f64::consts::FRAC_PI_4;
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, quarter_pi * (y / x))
+    } else {
+        (y, 2.0 * quarter_pi - quarter_pi * (x / y))
+    };
+    Vec3::new_use(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
 pub fn random_on_hemisphere(normal: Vec3) -> Vec3 {
     let on_unit_sphere = random_unit_vector();
     if dot(on_unit_sphere, normal) > 0.0 {
@@ -255,21 +277,27 @@ pub fn linear_to_gamma(linear_component: f64) -> f64 {
     0.0
 }
 
-// COLOR UTIL
-pub type Color = Vec3;
-
-pub fn write_color(pixel_color: Color) {
-    let r = linear_to_gamma(pixel_color.x() / 100.0);
-    let g = linear_to_gamma(pixel_color.y() / 100.0);
-    let b = linear_to_gamma(pixel_color.z() / 100.0);
-
-    // Translate the [0,1] component values to the byte range [0,255]
-    let intensity: Interval = Interval::new_use(0.000, 0.999);
-
-    let rbyte = (256.000 * intensity.clamp(r)) as i32;
-    let gbyte = (256.000 * intensity.clamp(g)) as i32;
-    let bbyte = (256.000 * intensity.clamp(b)) as i32;
+pub fn linear_to_srgb(linear_component: f64) -> f64 {
+    // Piecewise sRGB transfer function (IEC 61966-2-1)
+    if linear_component <= 0.0 {
+        return 0.0;
+    }
+    if linear_component <= 0.0031308 {
+        return 12.92 * linear_component;
+    }
+    1.055 * linear_component.powf(1.0 / 2.4) - 0.055
+}
 
-    // Write pixel color components
-    println!("{} {} {}", rbyte, gbyte, bbyte);
+pub fn srgb_to_linear(srgb_component: f64) -> f64 {
+    // Inverse of linear_to_srgb
+    if srgb_component <= 0.0 {
+        return 0.0;
+    }
+    if srgb_component <= 0.04045 {
+        return srgb_component / 12.92;
+    }
+    ((srgb_component + 0.055) / 1.055).powf(2.4)
 }
+
+// COLOR UTIL
+pub type Color = Vec3;